@@ -0,0 +1,83 @@
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::matcher::regex_error;
+
+// Like grep, a file with a NUL byte in its first block is treated as binary
+const BINARY_CHECK_LEN: usize = 8192;
+// How many characters of the line are shown, and how many of them go before the match
+const SNIPPET_LEN: usize = 120;
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Text searched inside the files that matched the name pattern
+#[derive(Debug, Clone)]
+pub struct ContentQuery {
+    regex: Regex,
+    search_binary: bool,
+    max_size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentMatch {
+    /// Starts at 1
+    pub line: u64,
+    pub snippet: String,
+}
+
+impl ContentQuery {
+    pub fn new(text: &str, is_regex: bool, search_binary: bool, max_size: u64) -> Result<Self, String> {
+        let pattern = if is_regex { String::from(text) } else { regex::escape(text) };
+        let regex = Regex::new(&pattern).map_err(regex_error)?;
+        Ok(ContentQuery { regex, search_binary, max_size })
+    }
+
+    /// First line of the file that matches, files bigger than the limit are skipped
+    pub fn search(&self, path: &Path, size: u64) -> Option<ContentMatch> {
+        if size > self.max_size {
+            return None;
+        }
+        let file = File::open(path).ok()?;
+        let mut reader = BufReader::with_capacity(BINARY_CHECK_LEN, file);
+
+        if !self.search_binary {
+            let head = reader.fill_buf().ok()?;
+            if head.contains(&0) {
+                return None;
+            }
+        }
+
+        let mut buffer = vec![];
+        let mut line = 0;
+        loop {
+            buffer.clear();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => line += 1,
+            }
+            // Without the line break, so the patterns that end with $ can match
+            let text = String::from_utf8_lossy(&buffer);
+            let text = text.strip_suffix('\n').map(|text| text.strip_suffix('\r').unwrap_or(text)).unwrap_or(&text);
+            if let Some(found) = self.regex.find(text) {
+                return Some(ContentMatch { line, snippet: snippet(text, found.start()) });
+            }
+        }
+    }
+}
+
+// A piece of the line around the match, so long lines still fit in the results
+fn snippet(line: &str, match_start: usize) -> String {
+    let line = line.trim_end();
+    let before = line[..match_start.min(line.len())].chars().count();
+    let skip = before.saturating_sub(SNIPPET_CONTEXT);
+
+    let mut snippet: String = line.chars().skip(skip).take(SNIPPET_LEN).collect();
+    if skip > 0 {
+        snippet.insert(0, '…');
+    }
+    if line.chars().count() > skip + SNIPPET_LEN {
+        snippet.push('…');
+    }
+    String::from(snippet.trim_start())
+}
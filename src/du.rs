@@ -0,0 +1,76 @@
+// Sizes added up per directory, like du does
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct DirUsage {
+    pub path: PathBuf,
+    /// Sum of the lengths of the files inside it, at any depth
+    pub apparent: u64,
+    /// Space taken on the disk by the blocks of those files
+    pub on_disk: u64,
+    /// The biggest ones go first
+    pub children: Vec<DirUsage>,
+}
+
+impl DirUsage {
+    fn build(path: &Path, totals: &HashMap<PathBuf, (u64, u64)>, subdirs: &HashMap<PathBuf, Vec<PathBuf>>) -> DirUsage {
+        let (apparent, on_disk) = totals.get(path).copied().unwrap_or_default();
+        let mut children: Vec<DirUsage> = subdirs.get(path)
+            .map(|dirs| dirs.iter().map(|dir| DirUsage::build(dir, totals, subdirs)).collect())
+            .unwrap_or_default();
+        sort_by_size(&mut children);
+        DirUsage { path: path.to_path_buf(), apparent, on_disk, children }
+    }
+
+    /// How many directories are in the tree, counting this one
+    pub fn count(&self) -> usize {
+        1 + self.children.iter().map(|child| child.count()).sum::<usize>()
+    }
+}
+
+fn sort_by_size(dirs: &mut [DirUsage]) {
+    dirs.sort_by(|a, b| b.apparent.cmp(&a.apparent).then_with(|| a.path.cmp(&b.path)));
+}
+
+/// One tree per root, the found entries are added to every directory between
+/// them and their root. The symlinks are not followed, and the hard links to
+/// the same file count only once
+pub fn disk_usage(roots: &[PathBuf], found: &[PathBuf]) -> Vec<DirUsage> {
+    let mut totals: HashMap<PathBuf, (u64, u64)> = roots.iter().map(|root| (root.clone(), (0, 0))).collect();
+    let mut subdirs: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    let mut inodes = HashSet::new();
+    for path in found {
+        let root = match roots.iter().find(|root| path.starts_with(root)) {
+            Some(root) => root,
+            None => continue,
+        };
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        // The directories are shown even when nothing inside them was found
+        let (size, dir) = if metadata.is_dir() {
+            ((0, 0), path.as_path())
+        } else if inodes.insert((metadata.dev(), metadata.ino())) {
+            ((metadata.len(), metadata.blocks() * 512), path.parent().unwrap_or(root))
+        } else {
+            continue;
+        };
+        for ancestor in dir.ancestors().take_while(|ancestor| ancestor.starts_with(root)) {
+            if !totals.contains_key(ancestor) {
+                if let Some(parent) = ancestor.parent() {
+                    subdirs.entry(parent.to_path_buf()).or_default().push(ancestor.to_path_buf());
+                }
+            }
+            let (apparent, on_disk) = totals.entry(ancestor.to_path_buf()).or_default();
+            *apparent += size.0;
+            *on_disk += size.1;
+        }
+    }
+    let mut usage: Vec<DirUsage> = roots.iter().map(|root| DirUsage::build(root, &totals, &subdirs)).collect();
+    sort_by_size(&mut usage);
+    usage
+}
@@ -0,0 +1,112 @@
+// Files with the same content, compared by size first, then by a hash of
+// their start and at last by a hash of the whole content
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// Most files that differ already differ in their first bytes
+const PARTIAL_HASH_LEN: u64 = 16 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Size of each copy
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Space freed by keeping only one copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+// `limit` is how many bytes are read from the start, None reads the whole file
+fn hash(path: &Path, limit: Option<u64>) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match limit {
+        Some(limit) => io::copy(&mut file.take(limit), &mut hasher)?,
+        None => io::copy(&mut file, &mut hasher)?,
+    };
+    Ok(hasher.finalize())
+}
+
+struct Hashing<'s, F> {
+    cancel: &'s AtomicBool,
+    on_progress: F,
+    hashed: usize,
+    total: usize,
+    last_report: Instant,
+}
+
+impl<F: Fn(usize, usize)> Hashing<'_, F> {
+    // Splits every group by the key of its files, the files that can't be read
+    // are left out and so are the groups left with only one file
+    fn split<K: Hash + Eq>(&mut self, groups: Vec<DuplicateGroup>, key: impl Fn(&Path) -> Option<K>) -> Vec<DuplicateGroup> {
+        let mut split = vec![];
+        for DuplicateGroup { size, paths } in groups {
+            let mut by_key: HashMap<K, Vec<PathBuf>> = HashMap::new();
+            for path in paths {
+                if self.cancel.load(Ordering::Relaxed) {
+                    return vec![];
+                }
+                if let Some(key) = key(&path) {
+                    by_key.entry(key).or_default().push(path);
+                }
+                self.hashed += 1;
+                if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+                    self.last_report = Instant::now();
+                    (self.on_progress)(self.hashed, self.total);
+                }
+            }
+            split.extend(by_key.into_values().filter(|paths| paths.len() > 1).map(|paths| DuplicateGroup { size, paths }));
+        }
+        split
+    }
+}
+
+/// The groups that waste the most space go first. Empty files are not
+/// compared, and the hard links to the same file count only once.
+/// `on_progress` gets how many files were hashed and how many will be
+pub fn find_duplicates<F: Fn(usize, usize)>(files: &[PathBuf], cancel: &AtomicBool, on_progress: F) -> Vec<DuplicateGroup> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut inodes = HashSet::new();
+    for path in files {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() > 0 => metadata,
+            _ => continue,
+        };
+        if inodes.insert((metadata.dev(), metadata.ino())) {
+            by_size.entry(metadata.len()).or_default().push(path.clone());
+        }
+    }
+    let same_size: Vec<DuplicateGroup> = by_size.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(size, paths)| DuplicateGroup { size, paths })
+        .collect();
+
+    let total = same_size.iter().map(|group| group.paths.len()).sum();
+    let mut hashing = Hashing { cancel, on_progress, hashed: 0, total, last_report: Instant::now() };
+
+    let same_start = hashing.split(same_size, |path| hash(path, Some(PARTIAL_HASH_LEN)).ok());
+    // The small files are read whole by the partial hash, only the big ones that are
+    // still together get the second pass
+    let (small, big): (Vec<DuplicateGroup>, Vec<DuplicateGroup>) = same_start.into_iter().partition(|group| group.size <= PARTIAL_HASH_LEN);
+    hashing.total += big.iter().map(|group| group.paths.len()).sum::<usize>();
+    let same_content = hashing.split(big, |path| hash(path, None).ok());
+    (hashing.on_progress)(hashing.hashed, hashing.total);
+
+    let mut groups: Vec<DuplicateGroup> = small.into_iter().chain(same_content).collect();
+    for group in groups.iter_mut() {
+        group.paths.sort();
+    }
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    groups
+}
@@ -0,0 +1,62 @@
+// Entries left behind that can usually be cleaned up
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct EmptyReport {
+    /// Regular files of zero bytes
+    pub files: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+    /// Symlinks that point to something that doesn't exist, with their targets
+    pub broken_links: Vec<(PathBuf, PathBuf)>,
+}
+
+// With `nested`, a directory that only holds empty directories is empty too
+fn is_empty_dir(path: &Path, nested: bool) -> bool {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    for entry in entries {
+        let is_empty_child = nested && entry.is_ok_and(|entry| {
+            entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && is_empty_dir(&entry.path(), nested)
+        });
+        if !is_empty_child {
+            return false;
+        }
+    }
+    true
+}
+
+impl EmptyReport {
+    /// Sorts the found entries, the ones that aren't empty are left out
+    pub fn new(found: &[PathBuf], nested_dirs: bool) -> Self {
+        let mut report = EmptyReport::default();
+        for path in found {
+            let metadata = match fs::symlink_metadata(path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_symlink() {
+                if fs::metadata(path).is_err() {
+                    report.broken_links.push((path.clone(), fs::read_link(path).unwrap_or_default()));
+                }
+            } else if metadata.is_file() {
+                if metadata.len() == 0 {
+                    report.files.push(path.clone());
+                }
+            } else if metadata.is_dir() && is_empty_dir(path, nested_dirs) {
+                report.dirs.push(path.clone());
+            }
+        }
+        report
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len() + self.dirs.len() + self.broken_links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
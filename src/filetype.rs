@@ -0,0 +1,42 @@
+// File types detected from the first bytes of the files, the extensions often lie or are missing
+use std::fs;
+use std::path::Path;
+
+/// MIME type of the file, like `image/png`. `None` for directories, unknown
+/// types and files that can't be read
+pub fn detect(path: &Path) -> Option<&'static str> {
+    // Opening a FIFO waits until something writes to it
+    if !fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        return None;
+    }
+    infer::get_from_path(path).ok().flatten().map(|kind| kind.mime_type())
+}
+
+/// MIME types like `application/pdf`, or families like `image/*` and `image`
+#[derive(Debug, Clone)]
+pub struct MimeQuery {
+    // Lowercase, the families are kept as `image/`
+    types: Vec<String>,
+}
+
+impl MimeQuery {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let types: Vec<String> = pattern.split("|")
+            .map(|mime| mime.trim().to_lowercase())
+            .filter(|mime| !mime.is_empty())
+            .map(|mime| match mime.strip_suffix("/*") {
+                Some(family) => format!("{}/", family),
+                None if !mime.contains('/') => format!("{}/", mime),
+                None => mime,
+            })
+            .collect();
+        if types.is_empty() {
+            return Err(String::from("Write some types, like image/* or application/pdf"));
+        }
+        Ok(MimeQuery { types })
+    }
+
+    pub fn matches(&self, mime: &str) -> bool {
+        self.types.iter().any(|wanted| if wanted.ends_with('/') { mime.starts_with(wanted.as_str()) } else { mime == wanted })
+    }
+}
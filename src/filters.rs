@@ -0,0 +1,436 @@
+// Filters applied on the metadata of the entries while walking
+use chrono::{DateTime, Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone};
+use ignore::overrides::{Override, OverrideBuilder};
+use std::fs::{self, FileType, Metadata};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Parses sizes like `512`, `10k`, `10M` or `2.5G`. The units are powers of
+/// 1024, same as the sizes shown in the results
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let unit_start = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+
+    let invalid = || format!("Invalid size \"{}\", use a number and a unit like 10M", text);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Both ends are inclusive, `None` means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizeRange {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl SizeRange {
+    pub fn new(min: Option<u64>, max: Option<u64>) -> Result<Self, String> {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(String::from("The minimum size is bigger than the maximum size"));
+            }
+        }
+        Ok(SizeRange { min, max })
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    pub fn contains(&self, size: u64) -> bool {
+        self.min.is_none_or(|min| size >= min) && self.max.is_none_or(|max| size <= max)
+    }
+}
+
+/// Depth of an entry under the search path, the entries directly inside it are at depth 1
+pub fn parse_depth(text: &str) -> Result<usize, String> {
+    text.trim().parse().map_err(|_| format!("Invalid depth \"{}\", use a whole number like 2", text.trim()))
+}
+
+/// Both ends are inclusive, `None` means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthRange {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+impl DepthRange {
+    pub fn new(min: Option<usize>, max: Option<usize>) -> Result<Self, String> {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(String::from("The minimum depth is bigger than the maximum depth"));
+            }
+        }
+        Ok(DepthRange { min, max })
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()
+}
+
+/// Parses dates like `2024-01-31` or `2024-01-31 14:30`, times ago like `7d`,
+/// `3 weeks ago` or `2mo`, and the words `now`, `today`, `yesterday`,
+/// `last week`, `last month` and `last year`
+pub fn parse_date(text: &str) -> Result<SystemTime, String> {
+    let now = Local::now();
+    let text = text.trim().to_lowercase();
+    let invalid = || format!("Invalid date \"{}\", use a date like 2024-01-31 or a time ago like 7d", text);
+
+    let named = match text.as_str() {
+        "now" => Some(now),
+        "today" => start_of_day(now.date_naive()),
+        "yesterday" => now.date_naive().pred_opt().and_then(start_of_day),
+        "last week" => now.checked_sub_signed(Duration::weeks(1)),
+        "last month" => now.checked_sub_months(Months::new(1)),
+        "last year" => now.checked_sub_months(Months::new(12)),
+        _ => None,
+    };
+    if let Some(date) = named {
+        return Ok(date.into());
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(&text, format) {
+            return Local.from_local_datetime(&date_time).earliest().map(SystemTime::from).ok_or_else(invalid);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return start_of_day(date).map(SystemTime::from).ok_or_else(invalid);
+    }
+
+    let ago = text.strip_suffix("ago").unwrap_or(&text).trim();
+    let unit_start = ago.find(|c: char| !c.is_ascii_digit()).unwrap_or(ago.len());
+    let (amount, unit) = ago.split_at(unit_start);
+    let amount: u32 = amount.parse().map_err(|_| invalid())?;
+    // Same units as humantime, m is minutes and mo is months
+    let seconds: i64 = match unit.trim() {
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        "mo" | "month" | "months" => {
+            return now.checked_sub_months(Months::new(amount)).map(SystemTime::from).ok_or_else(invalid);
+        }
+        "y" | "year" | "years" => {
+            let months = amount.checked_mul(12).ok_or_else(invalid)?;
+            return now.checked_sub_months(Months::new(months)).map(SystemTime::from).ok_or_else(invalid);
+        }
+        _ => return Err(invalid()),
+    };
+    now.checked_sub_signed(Duration::seconds(amount as i64 * seconds))
+        .map(SystemTime::from)
+        .ok_or_else(invalid)
+}
+
+/// Both ends are inclusive, `None` means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateRange {
+    pub after: Option<SystemTime>,
+    pub before: Option<SystemTime>,
+}
+
+impl DateRange {
+    pub fn new(after: Option<SystemTime>, before: Option<SystemTime>) -> Result<Self, String> {
+        if let (Some(after), Some(before)) = (after, before) {
+            if after > before {
+                return Err(String::from("The \"after\" date is later than the \"before\" date"));
+            }
+        }
+        Ok(DateRange { after, before })
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.after.is_some() || self.before.is_some()
+    }
+
+    // Entries without the date (some filesystems don't keep the creation date) never match
+    fn contains(&self, time: Option<SystemTime>) -> bool {
+        if !self.is_set() {
+            return true;
+        }
+        match time {
+            Some(time) => self.after.is_none_or(|after| time >= after) && self.before.is_none_or(|before| time <= before),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateFilters {
+    pub modified: DateRange,
+    pub created: DateRange,
+    pub accessed: DateRange,
+}
+
+impl DateFilters {
+    pub fn is_set(&self) -> bool {
+        self.modified.is_set() || self.created.is_set() || self.accessed.is_set()
+    }
+
+    pub fn contains(&self, metadata: &Metadata) -> bool {
+        self.modified.contains(metadata.modified().ok())
+            && self.created.contains(metadata.created().ok())
+            && self.accessed.contains(metadata.accessed().ok())
+    }
+}
+
+/// What an entry is, symlinks are told apart from what they point to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Executable,
+    Directory,
+    Symlink,
+    // Points to something that doesn't exist
+    BrokenSymlink,
+    Fifo,
+    Socket,
+    Device,
+}
+
+impl EntryKind {
+    /// `file_type` is the one of the entry itself, not the one of the symlink target.
+    /// The metadata is only read for symlinks and regular files
+    pub fn new(path: &Path, file_type: FileType) -> Self {
+        if file_type.is_dir() {
+            EntryKind::Directory
+        } else if file_type.is_symlink() {
+            if fs::metadata(path).is_ok() { EntryKind::Symlink } else { EntryKind::BrokenSymlink }
+        } else if file_type.is_fifo() {
+            EntryKind::Fifo
+        } else if file_type.is_socket() {
+            EntryKind::Socket
+        } else if file_type.is_block_device() || file_type.is_char_device() {
+            EntryKind::Device
+        } else if fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0) {
+            EntryKind::Executable
+        } else {
+            EntryKind::File
+        }
+    }
+
+    pub fn of_path(path: &Path) -> Option<Self> {
+        fs::symlink_metadata(path).ok().map(|metadata| EntryKind::new(path, metadata.file_type()))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntryKind::File => "File",
+            EntryKind::Executable => "Executable",
+            EntryKind::Directory => "Directory",
+            EntryKind::Symlink => "Symlink",
+            EntryKind::BrokenSymlink => "Broken symlink",
+            EntryKind::Fifo => "FIFO",
+            EntryKind::Socket => "Socket",
+            EntryKind::Device => "Device",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TypeFilter {
+    #[default]
+    Any,
+    Files,
+    Directories,
+    Symlinks,
+    BrokenSymlinks,
+    Executables,
+    // FIFOs, sockets and devices
+    Special,
+}
+
+impl TypeFilter {
+    // Same order as the entry type selector of the main page
+    pub const ALL: [TypeFilter; 7] = [
+        TypeFilter::Any,
+        TypeFilter::Files,
+        TypeFilter::Directories,
+        TypeFilter::Symlinks,
+        TypeFilter::BrokenSymlinks,
+        TypeFilter::Executables,
+        TypeFilter::Special,
+    ];
+
+    pub fn from_index(index: u32) -> TypeFilter {
+        TypeFilter::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    /// Words like `dir` or `exec`, used by the queries
+    pub fn from_name(name: &str) -> Result<TypeFilter, String> {
+        match name.to_lowercase().as_str() {
+            "any" => Ok(TypeFilter::Any),
+            "f" | "file" | "files" => Ok(TypeFilter::Files),
+            "d" | "dir" | "dirs" | "directory" | "directories" => Ok(TypeFilter::Directories),
+            "l" | "link" | "symlink" | "symlinks" => Ok(TypeFilter::Symlinks),
+            "broken" => Ok(TypeFilter::BrokenSymlinks),
+            "x" | "exec" | "executable" | "executables" => Ok(TypeFilter::Executables),
+            "special" | "fifo" | "socket" | "device" => Ok(TypeFilter::Special),
+            _ => Err(format!("Unknown type \"{}\", use file, dir, symlink, broken, exec or special", name)),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TypeFilter::Any => "Anything",
+            TypeFilter::Files => "Files",
+            TypeFilter::Directories => "Directories",
+            TypeFilter::Symlinks => "Symlinks",
+            TypeFilter::BrokenSymlinks => "Broken symlinks",
+            TypeFilter::Executables => "Executables",
+            TypeFilter::Special => "FIFOs, sockets and devices",
+        }
+    }
+
+    pub fn contains(&self, kind: EntryKind) -> bool {
+        match self {
+            TypeFilter::Any => true,
+            TypeFilter::Files => matches!(kind, EntryKind::File | EntryKind::Executable),
+            TypeFilter::Directories => kind == EntryKind::Directory,
+            TypeFilter::Symlinks => matches!(kind, EntryKind::Symlink | EntryKind::BrokenSymlink),
+            TypeFilter::BrokenSymlinks => kind == EntryKind::BrokenSymlink,
+            TypeFilter::Executables => kind == EntryKind::Executable,
+            TypeFilter::Special => matches!(kind, EntryKind::Fifo | EntryKind::Socket | EntryKind::Device),
+        }
+    }
+}
+
+/// A user name or a numeric id
+pub fn parse_owner(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    text.parse()
+        .ok()
+        .or_else(|| uzers::get_user_by_name(text).map(|user| user.uid()))
+        .ok_or_else(|| format!("Unknown user \"{}\"", text))
+}
+
+/// A group name or a numeric id
+pub fn parse_group(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    text.parse()
+        .ok()
+        .or_else(|| uzers::get_group_by_name(text).map(|group| group.gid()))
+        .ok_or_else(|| format!("Unknown group \"{}\"", text))
+}
+
+/// Octal permission bits like `755`, `0644` or `4755`
+pub fn parse_mode(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    u32::from_str_radix(text.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("Invalid mode \"{}\", use octal digits like 755 or 0644", text))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PermissionCheck {
+    #[default]
+    Any,
+    WorldWritable,
+    // Setuid or setgid
+    SetId,
+    // By the user running the search
+    NotReadable,
+}
+
+impl PermissionCheck {
+    // Same order as the permission selector of the main page
+    pub const ALL: [PermissionCheck; 4] = [PermissionCheck::Any, PermissionCheck::WorldWritable, PermissionCheck::SetId, PermissionCheck::NotReadable];
+
+    pub fn from_index(index: u32) -> PermissionCheck {
+        PermissionCheck::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PermissionCheck::Any => "Any permissions",
+            PermissionCheck::WorldWritable => "Writable by everyone",
+            PermissionCheck::SetId => "Setuid or setgid",
+            PermissionCheck::NotReadable => "Not readable by me",
+        }
+    }
+}
+
+/// Owner, group and permission bits of the entries
+#[derive(Debug, Clone, Default)]
+pub struct PermissionFilters {
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+    /// The exact permission bits, with the setuid, setgid and sticky ones
+    pub mode: Option<u32>,
+    pub check: PermissionCheck,
+    // Who runs the search, for PermissionCheck::NotReadable
+    uid: u32,
+    gids: Vec<u32>,
+}
+
+impl PermissionFilters {
+    pub fn new(owner: Option<u32>, group: Option<u32>, mode: Option<u32>, check: PermissionCheck) -> Self {
+        let mut gids: Vec<u32> = uzers::group_access_list().unwrap_or_default().iter().map(|group| group.gid()).collect();
+        gids.push(uzers::get_effective_gid());
+        PermissionFilters { owner, group, mode, check, uid: uzers::get_effective_uid(), gids }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.owner.is_some() || self.group.is_some() || self.mode.is_some() || self.check != PermissionCheck::Any
+    }
+
+    /// `metadata` is the one of the symlink target, the mode of a link itself is always 777
+    pub fn contains(&self, metadata: &Metadata) -> bool {
+        let mode = metadata.mode();
+        let check = match self.check {
+            PermissionCheck::Any => true,
+            PermissionCheck::WorldWritable => mode & 0o002 != 0,
+            PermissionCheck::SetId => mode & 0o6000 != 0,
+            // Root reads everything
+            PermissionCheck::NotReadable if self.uid == 0 => false,
+            PermissionCheck::NotReadable => {
+                let read_bit = if metadata.uid() == self.uid {
+                    0o400
+                } else if self.gids.contains(&metadata.gid()) {
+                    0o040
+                } else {
+                    0o004
+                };
+                mode & read_bit == 0
+            }
+        };
+        check
+            && self.owner.is_none_or(|owner| metadata.uid() == owner)
+            && self.group.is_none_or(|group| metadata.gid() == group)
+            && self.mode.is_none_or(|wanted| mode & 0o7777 == wanted)
+    }
+}
+
+/// Splits a list of patterns separated by |, like the extensions
+pub fn split_patterns(text: &str) -> Vec<String> {
+    text.split("|")
+        .map(|pattern| pattern.trim())
+        .filter(|pattern| !pattern.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Directory names and globs like `node_modules`, `**/.venv` or `*.min.js`,
+/// written like gitignore lines. The walker doesn't enter the directories
+/// that match
+pub fn build_excludes(root: &Path, patterns: &[String]) -> Result<Override, String> {
+    let mut builder = OverrideBuilder::new(root);
+    for pattern in patterns {
+        // In overrides the ! means ignore, it's the other way around than in gitignore files
+        builder.add(&format!("!{}", pattern))
+            .map_err(|err| format!("Invalid exclude pattern \"{}\": {}", pattern, err))?;
+    }
+    builder.build().map_err(|err| err.to_string())
+}
@@ -1,48 +1,452 @@
-use walkdir::WalkDir;
-use std::path::PathBuf;
+use ignore::{WalkBuilder, WalkState, ParallelVisitor, ParallelVisitorBuilder, DirEntry};
+use ignore::overrides::Override;
+use log::{info, warn};
+use std::fs::{self, FileType};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use std::collections::HashMap;
 
+use crate::content::{ContentMatch, ContentQuery};
+use crate::filetype;
+use crate::filters::{self, DateFilters, DepthRange, EntryKind, PermissionFilters, SizeRange, TypeFilter};
+use crate::matcher::NameMatcher;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct FinderOptions {
+    /// Number of walker threads, 0 lets the walker use one per core
+    pub threads: usize,
+    /// Text the matched files must contain, `None` to only look at the names
+    pub content: Option<ContentQuery>,
+    pub size: SizeRange,
+    pub dates: DateFilters,
+    pub entry_type: TypeFilter,
+    pub permissions: PermissionFilters,
+    /// The deeper directories are not entered, the shallower entries are walked but not matched
+    pub depth: DepthRange,
+    /// When false, hidden entries are skipped and hidden directories are not entered
+    pub include_hidden: bool,
+    /// Skip what .gitignore, .ignore, .fdignore and the global git excludes
+    /// ignore, same as ripgrep and fd
+    pub respect_ignore_files: bool,
+    /// Read the start of the matched files to know their type, see `filetype::detect`
+    pub detect_types: bool,
+    /// Extra gitignore style file, only used with `respect_ignore_files`
+    pub custom_ignore_file: Option<PathBuf>,
+    /// Enter the directories behind symlinks, the loops are skipped and counted
+    pub follow_links: bool,
+    /// Don't cross into other mounted filesystems, like /proc or network mounts
+    pub same_file_system: bool,
+    /// Patterns of the entries that are skipped, see `filters::build_excludes`
+    pub exclude: Vec<String>,
+}
+
+// A batch is sent when it is this big or when it waited this long, whatever comes first
+const BATCH_SIZE: usize = 256;
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum FinderEvent {
+    /// A batch of matches found while walking, in walk order
+    Matches(Vec<FoundEntry>),
+    /// How far the walk is, sent every few moments and once more at the end
+    Progress(FinderProgress),
+}
+
+#[derive(Debug, Clone)]
+pub struct FoundEntry {
+    pub path: PathBuf,
+    /// The search root the entry was found under
+    pub root: PathBuf,
+    /// Relevance of the match in the fuzzy mode, higher is better
+    pub score: Option<i64>,
+    /// Where the text was found, when searching inside the files
+    pub content: Option<ContentMatch>,
+    /// MIME type found in the start of the file
+    pub mime: Option<&'static str>,
+    pub kind: EntryKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FinderProgress {
+    pub dirs_visited: u64,
+    pub entries_seen: u64,
+    pub matches: u64,
+    /// Symlinks that point back to one of their parent directories
+    pub loops: u64,
+    /// Directory being scanned by one of the threads, empty when the walk ended
+    pub current_dir: PathBuf,
+    pub elapsed: Duration,
+}
+
+// Shared by all the walker threads
+struct Counters {
+    dirs_visited: AtomicU64,
+    entries_seen: AtomicU64,
+    matches: AtomicU64,
+    loops: AtomicU64,
+    started: Instant,
+    last_report: Mutex<Instant>,
+}
+
+impl Counters {
+    fn new() -> Self {
+        let now = Instant::now();
+        Counters {
+            dirs_visited: AtomicU64::new(0),
+            entries_seen: AtomicU64::new(0),
+            matches: AtomicU64::new(0),
+            loops: AtomicU64::new(0),
+            started: now,
+            last_report: Mutex::new(now),
+        }
+    }
+
+    fn snapshot(&self, current_dir: PathBuf) -> FinderProgress {
+        FinderProgress {
+            dirs_visited: self.dirs_visited.load(Ordering::Relaxed),
+            entries_seen: self.entries_seen.load(Ordering::Relaxed),
+            matches: self.matches.load(Ordering::Relaxed),
+            loops: self.loops.load(Ordering::Relaxed),
+            current_dir,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+// The walker wraps the errors with the path and depth where they happened
+fn find_loop(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop { ancestor, child } => Some((ancestor, child)),
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => find_loop(err),
+        _ => None,
+    }
+}
+
+// With follow_links the symlinks that point to nothing come as errors, with
+// the path and depth they were found at
+fn find_broken_link(err: &ignore::Error) -> Option<(&Path, usize)> {
+    match err {
+        ignore::Error::WithPath { path, err } => match err.as_ref() {
+            ignore::Error::WithDepth { depth, .. } if path.is_symlink() && fs::metadata(path).is_err() => Some((path, *depth)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The excludes are built for every root, the patterns with a slash are relative to it
+fn is_excluded(excludes: &[(PathBuf, Override)], path: &Path, is_dir: bool) -> bool {
+    excludes.iter()
+        .find(|(root, _)| path.starts_with(root))
+        .is_some_and(|(_, excludes)| excludes.matched(path, is_dir).is_ignore())
+}
+
+// Keeps the roots in the given order, without the repeated ones and the ones
+// inside another root, so no entry is walked twice
+fn distinct_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let canonical: Vec<PathBuf> = roots.iter().map(|root| root.canonicalize().unwrap_or_else(|_| root.clone())).collect();
+    let mut distinct = vec![];
+    for (pos, root) in roots.iter().enumerate() {
+        let overlaps = canonical.iter().enumerate().any(|(other, other_root)| {
+            // Between two equal roots the first one is kept
+            other != pos && canonical[pos].starts_with(other_root) && (canonical[pos] != *other_root || other < pos)
+        });
+        if overlaps {
+            info!("finder::distinct_roots: Skipping {}, it's already inside another root", root.to_string_lossy());
+        } else {
+            distinct.push(root.clone());
+        }
+    }
+    distinct
+}
 
 #[derive(Debug)]
 pub struct Finder {
-    path: String,
-    exts: Option<Vec<String>>,
+    roots: Vec<PathBuf>,
+    matcher: NameMatcher,
     files_by_ext: Option<HashMap<String, Vec<PathBuf>>>,
-    all: Option<Vec<PathBuf>>
+    all: Option<Vec<PathBuf>>,
+    options: FinderOptions,
 }
 
-impl Finder {
-    pub fn new(path: String, extension_filter: String) -> Self {
-        let exts_vec: Vec<&str> = extension_filter.split("|").collect();
-        let vector_string: Vec<String> = exts_vec.iter().map(|&s| String::from(s)).collect();
+// Every walker thread gets its own visitor, matches are kept locally and
+// flushed in batches to the shared vector and to the event callback
+struct FindVisitorBuilder<'s, F> {
+    roots: &'s [PathBuf],
+    matcher: &'s NameMatcher,
+    options: &'s FinderOptions,
+    // None when the matches are only sent to the event callback
+    found: Option<&'s Mutex<Vec<PathBuf>>>,
+    excludes: &'s [(PathBuf, Override)],
+    cancel: &'s AtomicBool,
+    counters: &'s Counters,
+    on_event: F,
+}
 
-        Finder { path: path, exts: Some(vector_string), files_by_ext: Some(HashMap::new()), all: Some(vec![])}
+impl<'s, F> ParallelVisitorBuilder<'s> for FindVisitorBuilder<'s, F>
+where
+    F: Fn(FinderEvent) + Send + Clone + 's,
+{
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(FindVisitor {
+            roots: self.roots,
+            matcher: self.matcher,
+            options: self.options,
+            found: self.found,
+            excludes: self.excludes,
+            cancel: self.cancel,
+            counters: self.counters,
+            on_event: self.on_event.clone(),
+            batch: vec![],
+            current_dir: PathBuf::new(),
+            last_flush: Instant::now(),
+        })
     }
+}
 
-    pub fn find(&mut self) -> Result<HashMap<String, Vec<PathBuf>>, ()> {
-        let mut files_by_ext: HashMap<String, Vec<PathBuf>> = HashMap::new();
-        for entry in WalkDir::new(&self.path).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.starts_with(".") {
-                continue;
+struct FindVisitor<'s, F: Fn(FinderEvent)> {
+    roots: &'s [PathBuf],
+    matcher: &'s NameMatcher,
+    options: &'s FinderOptions,
+    found: Option<&'s Mutex<Vec<PathBuf>>>,
+    // The ones of every root, see `is_excluded`
+    excludes: &'s [(PathBuf, Override)],
+    cancel: &'s AtomicBool,
+    counters: &'s Counters,
+    on_event: F,
+    batch: Vec<FoundEntry>,
+    current_dir: PathBuf,
+    last_flush: Instant,
+}
+
+impl<F: Fn(FinderEvent)> FindVisitor<'_, F> {
+    fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.batch.is_empty() {
+            return;
+        }
+        if let Some(Ok(mut found)) = self.found.map(|found| found.lock()) {
+            found.extend(self.batch.iter().map(|entry| entry.path.clone()));
+        }
+        (self.on_event)(FinderEvent::Matches(std::mem::take(&mut self.batch)));
+    }
+
+    // The broken links found while following the symlinks come before the walker
+    // filters them, so the hidden, depth and exclude filters are checked here again
+    fn is_walked(&self, path: &Path, depth: usize) -> bool {
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        (self.options.include_hidden || !hidden)
+            && self.options.depth.min.is_none_or(|min| depth >= min)
+            && self.options.depth.max.is_none_or(|max| depth <= max)
+            && !is_excluded(self.excludes, path, false)
+    }
+
+    /// `file_type` is the one given by the walker, the one of the target when following
+    /// the symlinks. `is_symlink` tells if the entry itself is a symlink
+    fn check(&self, path: &Path, depth: usize, file_type: FileType, is_symlink: bool) -> Option<FoundEntry> {
+        // The roots themselves are not results, only what is under them
+        if depth == 0 {
+            return None;
+        }
+        let root = self.roots.iter().find(|root| path.starts_with(root))?;
+        let is_file = file_type.is_file();
+        let mut mime = None;
+        let score = match self.matcher {
+            // The fuzzy query is scored without the search root, it's the same for every entry
+            NameMatcher::Fuzzy(query) => Some(query.score(path.strip_prefix(root).unwrap_or(path))?),
+            // Same for the globs like src/*.rs
+            NameMatcher::Glob(glob, true) => glob.is_match(path.strip_prefix(root).unwrap_or(path)).then_some(None)?,
+            // The type is kept for the results, so the file isn't read twice
+            NameMatcher::Mime(query) => {
+                mime = Some(filetype::detect(path).filter(|mime| query.matches(mime))?);
+                None
             }
-            if let Some(ext) = path.extension() {
-                let ext = ext.to_string_lossy().to_string();
-                if let Some(exts) = &self.exts {
-                    if exts.iter().any(|e| e == &ext) {
-                        files_by_ext.entry(ext).or_insert(vec![]).push(path.to_path_buf());
-                        if let Some(all) = self.all.as_mut() {
-                            all.push(path.to_path_buf());
-                        }
+            matcher => matcher.is_match(path).then_some(None)?,
+        };
+
+        let options = self.options;
+        // The followed symlinks have the type of their target, the type filter needs the link
+        let kind = if is_symlink { EntryKind::of_path(path)? } else { EntryKind::new(path, file_type) };
+        if !options.entry_type.contains(kind) {
+            return None;
+        }
+        // The size and content filters only make sense on files
+        if (options.size.is_set() || options.content.is_some()) && !is_file {
+            return None;
+        }
+        let mut size = 0;
+        if options.size.is_set() || options.content.is_some() || options.dates.is_set() || options.permissions.is_set() {
+            // The symlinks are resolved like in the query and the result columns, the
+            // mode of the link itself is always 777. The broken ones have nothing to check
+            let metadata = fs::metadata(path).ok()?;
+            if !options.size.contains(metadata.len()) || !options.dates.contains(&metadata) || !options.permissions.contains(&metadata) {
+                return None;
+            }
+            size = metadata.len();
+        }
+        let content = match &options.content {
+            Some(query) => Some(query.search(path, size)?),
+            None => None,
+        };
+        if mime.is_none() && options.detect_types && is_file {
+            mime = filetype::detect(path);
+        }
+        Some(FoundEntry { path: path.to_path_buf(), root: root.clone(), score, content, mime, kind })
+    }
+
+    // Only one thread reports at a time, the others just skip it
+    fn report(&self) {
+        if let Ok(mut last_report) = self.counters.last_report.try_lock() {
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                *last_report = Instant::now();
+                (self.on_event)(FinderEvent::Progress(self.counters.snapshot(self.current_dir.clone())));
+            }
+        }
+    }
+}
+
+impl<F: Fn(FinderEvent) + Send> ParallelVisitor for FindVisitor<'_, F> {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        // Quitting stops every thread, what is left in the batches is flushed on drop
+        if self.cancel.load(Ordering::Relaxed) {
+            return WalkState::Quit;
+        }
+        let found = match entry {
+            Ok(entry) => {
+                let path = entry.path();
+                self.counters.entries_seen.fetch_add(1, Ordering::Relaxed);
+                if entry.file_type().is_some_and(|t| t.is_dir()) {
+                    self.counters.dirs_visited.fetch_add(1, Ordering::Relaxed);
+                    self.current_dir = path.to_path_buf();
+                }
+                entry.file_type().and_then(|file_type| self.check(path, entry.depth(), file_type, entry.path_is_symlink()))
+            }
+            Err(err) => {
+                if let Some((ancestor, child)) = find_loop(&err) {
+                    warn!("finder::FindVisitor::visit: Symlink loop skipped, {} points to {}", child.to_string_lossy(), ancestor.to_string_lossy());
+                    self.counters.loops.fetch_add(1, Ordering::Relaxed);
+                }
+                match find_broken_link(&err) {
+                    Some((path, depth)) if self.is_walked(path, depth) => {
+                        self.counters.entries_seen.fetch_add(1, Ordering::Relaxed);
+                        fs::symlink_metadata(path).ok().and_then(|metadata| self.check(path, depth, metadata.file_type(), true))
                     }
+                    _ => None,
+                }
+            }
+        };
+        if let Some(found) = found {
+            self.counters.matches.fetch_add(1, Ordering::Relaxed);
+            self.batch.push(found);
+        }
+        if self.batch.len() >= BATCH_SIZE || self.last_flush.elapsed() >= BATCH_INTERVAL {
+            self.flush();
+            self.report();
+        }
+        WalkState::Continue
+    }
+}
+
+impl<F: Fn(FinderEvent)> Drop for FindVisitor<'_, F> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl Finder {
+    /// The roots are searched in the same walk, see `distinct_roots` for the overlapping ones
+    pub fn new(roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions) -> Self {
+        Finder { roots: distinct_roots(roots), matcher, files_by_ext: Some(HashMap::new()), all: Some(vec![]), options }
+    }
+
+    /// `on_event` is called from the walker threads with the matches found
+    /// so far, so they can be shown before the walk ends. Setting `cancel`
+    /// stops the walk, the matches found until then are still counted. Returns
+    /// how many matches were found, see `get_all` for the paths
+    pub fn find<F>(&mut self, cancel: &AtomicBool, on_event: F) -> Result<usize, ()>
+    where
+        F: Fn(FinderEvent) + Send + Clone,
+    {
+        let mut files_by_ext: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        let found = self.all.is_some().then(|| Mutex::new(vec![]));
+        let counters = Counters::new();
+
+        // The patterns with a slash are relative to a root, so every root gets its own excludes
+        let excludes: Result<Vec<(PathBuf, Override)>, String> = self.roots.iter()
+            .map(|root| filters::build_excludes(root, &self.options.exclude).map(|excludes| (root.clone(), excludes)))
+            .collect();
+        let excludes = Arc::new(excludes.unwrap_or_else(|err| {
+            warn!("finder::Finder::find: Excludes not applied: {}", err);
+            vec![]
+        }));
+
+        let (first_root, other_roots) = self.roots.split_first().ok_or(())?;
+        let mut visitor_builder = FindVisitorBuilder { roots: &self.roots, matcher: &self.matcher, options: &self.options, found: found.as_ref(), excludes: &excludes, cancel, counters: &counters, on_event };
+        let respect_ignore_files = self.options.respect_ignore_files;
+        let mut walk_builder = WalkBuilder::new(first_root);
+        for root in other_roots {
+            walk_builder.add(root);
+        }
+        walk_builder
+            .standard_filters(false)
+            .hidden(!self.options.include_hidden)
+            .ignore(respect_ignore_files)
+            .parents(respect_ignore_files)
+            .git_ignore(respect_ignore_files)
+            .git_global(respect_ignore_files)
+            .git_exclude(respect_ignore_files)
+            .follow_links(self.options.follow_links)
+            .same_file_system(self.options.same_file_system)
+            .min_depth(self.options.depth.min)
+            .max_depth(self.options.depth.max)
+            .threads(self.options.threads);
+        if respect_ignore_files {
+            walk_builder.add_custom_ignore_filename(".fdignore");
+            if let Some(ignore_file) = &self.options.custom_ignore_file {
+                if let Some(err) = walk_builder.add_ignore(ignore_file) {
+                    warn!("finder::Finder::find: Error in the ignore file {}: {}", ignore_file.to_string_lossy(), err);
                 }
             }
         }
-        if files_by_ext.is_empty() {
+        if !self.options.exclude.is_empty() {
+            let excludes = Arc::clone(&excludes);
+            walk_builder.filter_entry(move |entry| {
+                !is_excluded(&excludes, entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+            });
+        }
+        walk_builder
+            .build_parallel()
+            .visit(&mut visitor_builder);
+        (visitor_builder.on_event)(FinderEvent::Progress(counters.snapshot(PathBuf::new())));
+
+        let total = counters.matches.load(Ordering::Relaxed) as usize;
+        if total == 0 {
             return Err(());
         }
-        Ok(files_by_ext)
+        // The threads finish in any order, sorting keeps the reports the same between runs
+        let mut found = found.and_then(|found| found.into_inner().ok()).unwrap_or_default();
+        found.sort();
+        for path in found {
+            if let Some(ext) = path.extension() {
+                let ext = ext.to_string_lossy().to_string();
+                files_by_ext.entry(ext).or_insert(vec![]).push(path.clone());
+            }
+            if let Some(all) = self.all.as_mut() {
+                all.push(path);
+            }
+        }
+        self.files_by_ext = Some(files_by_ext);
+        Ok(total)
+    }
+
+    /// The matches are only sent to the event callback, `get_all` returns nothing
+    pub fn without_results(mut self) -> Self {
+        self.all = None;
+        self
     }
 
     pub fn get_all(&self) -> Vec<PathBuf> {
@@ -53,9 +457,14 @@ impl Finder {
         // } else {
         //     vec![]
         // }
-        self.all.clone().unwrap()
+        self.all.clone().unwrap_or_default()
     }
-    
+
+    /// The roots that are walked, without the overlapping ones
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
     pub fn filter(&mut self, keyword: &str) -> Vec<PathBuf> {
         let mut matches = vec![];
         if let Some(files) = self.all.as_ref() {
@@ -69,4 +478,63 @@ impl Finder {
         }
         return matches;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    // A temp dir of its own for every test, removed when the test ends, even when it fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("finder-test-{}-{}", std::process::id(), test));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn broken_link(&self, path: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            symlink("/nonexistent", path).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn find_all(root: &Path, options: FinderOptions) -> Vec<PathBuf> {
+        let mut finder = Finder::new(vec![root.to_path_buf()], NameMatcher::All, options);
+        let _ = finder.find(&AtomicBool::new(false), |_| {});
+        finder.get_all().iter().map(|path| path.strip_prefix(root).unwrap().to_path_buf()).collect()
+    }
+
+    #[test]
+    fn followed_broken_links_are_filtered() {
+        let dir = TempDir::new("broken-links");
+        dir.broken_link(".hiddenbroken");
+        dir.broken_link("top");
+        dir.broken_link("a/b/c/deep");
+        dir.broken_link("a/b/c/skipped");
+        let options = || FinderOptions { follow_links: true, entry_type: TypeFilter::BrokenSymlinks, ..Default::default() };
+
+        assert_eq!(find_all(&dir.0, options()), [Path::new("a/b/c/deep"), Path::new("a/b/c/skipped"), Path::new("top")]);
+
+        let found = find_all(&dir.0, FinderOptions { include_hidden: true, ..options() });
+        assert!(found.contains(&PathBuf::from(".hiddenbroken")));
+
+        let found = find_all(&dir.0, FinderOptions { depth: DepthRange::new(Some(4), None).unwrap(), ..options() });
+        assert_eq!(found, [Path::new("a/b/c/deep"), Path::new("a/b/c/skipped")]);
+
+        let found = find_all(&dir.0, FinderOptions { depth: DepthRange::new(None, Some(1)).unwrap(), ..options() });
+        assert_eq!(found, [Path::new("top")]);
+
+        let found = find_all(&dir.0, FinderOptions { exclude: vec![String::from("skipped"), String::from("/top")], ..options() });
+        assert_eq!(found, [Path::new("a/b/c/deep")]);
+    }
+}
@@ -1,18 +1,37 @@
-use adw::{ApplicationWindow, EntryRow, HeaderBar, prelude::*};
-use gtk::{Application, ListBox, Box as GtkBox, Orientation, ProgressBar, Label, Button, TreeView, glib, Notebook};
+use adw::{ActionRow, ApplicationWindow, ComboRow, EntryRow, ExpanderRow, HeaderBar, prelude::*};
+use gtk::{Application, ListBox, Box as GtkBox, Orientation, ProgressBar, Label, Button, TreeView, glib, Notebook, Switch, ToggleButton};
 use log::{info, error};
-use std::path::PathBuf;
 use std::thread;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-mod setup; mod finder; mod stated;
+mod setup; mod finder; mod stated; mod matcher; mod content; mod filters; mod filetype; mod query; mod duplicates; mod empty; mod du;
 
 use glib::Sender;
 
-use finder::Finder;
+use finder::{Finder, FinderEvent, FinderOptions, FinderProgress, FoundEntry};
+use matcher::{EXTENSION_CATEGORIES, MatchMode, NameMatcher};
+use content::ContentQuery;
+use duplicates::DuplicateGroup;
+use empty::EmptyReport;
+use du::DirUsage;
+use filters::{DateFilters, DateRange, DepthRange, PermissionCheck, PermissionFilters, SizeRange, TypeFilter};
 
 
 enum SendTypes {
-    VectorValue(Vec<PathBuf>),
+    // A batch of files found while the search is still running
+    VectorValue(Vec<FoundEntry>),
+    // The search ended, with the total of files found
+    Finished(usize),
+    // The user stopped the search, with the total of files found until then
+    Cancelled(usize),
+    Progress(FinderProgress),
+    // Shown under the progress bar while a report is made after the walk
+    Status(String),
+    Duplicates(Vec<DuplicateGroup>),
+    Empty(EmptyReport),
+    DiskUsage(Vec<DirUsage>),
     Error(String),
     Bool(bool),
 }
@@ -20,8 +39,258 @@ enum SendTypes {
 struct Callbacks {
     gtk_box: GtkBox,
     nb: Notebook,
-    path: String,
-    exts: String,
+    roots: Vec<PathBuf>,
+    matcher: NameMatcher,
+    options: FinderOptions,
+    report: Report,
+}
+
+// What the search shows, the files found or a report made from them
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Report {
+    #[default]
+    Files,
+    Duplicates,
+    // The bool is true when the directories that only hold empty directories count as empty
+    Empty(bool),
+    DiskUsage,
+}
+
+impl Report {
+    // Same order as the report selector of the main page
+    const ALL: [Report; 4] = [Report::Files, Report::Duplicates, Report::Empty(false), Report::DiskUsage];
+
+    fn from_index(index: u32) -> Report {
+        Report::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    // The maintenance reports look at every entry, the match mode doesn't apply to them
+    fn uses_matcher(&self) -> bool {
+        matches!(self, Report::Files | Report::Duplicates)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Report::Files => "Matching files",
+            Report::Duplicates => "Duplicated files",
+            Report::Empty(_) => "Empty files, empty directories and broken symlinks",
+            Report::DiskUsage => "Disk usage per directory",
+        }
+    }
+
+    // Subtitle of the report selector, what the report looks at
+    fn hint(&self) -> &'static str {
+        match self {
+            Report::Files | Report::Duplicates => "Only the entries that match the search are shown",
+            Report::Empty(_) => "Every entry is checked. Only the depth, the excludes and the switches of this page apply",
+            Report::DiskUsage => "Every file is counted, hidden and ignored ones too. Only the excludes and the symlink and filesystem switches apply",
+        }
+    }
+}
+
+// The inputs of the main page
+#[derive(Clone)]
+struct SearchForm {
+    configs: setup::YamlConfiguration,
+    mode_row: ComboRow,
+    report_row: ComboRow,
+    nested_empty_switch: Switch,
+    pattern_row: EntryRow,
+    path_row: EntryRow,
+    content_row: EntryRow,
+    type_row: ComboRow,
+    exclude_row: EntryRow,
+    min_depth_row: EntryRow,
+    max_depth_row: EntryRow,
+    case_btt: ToggleButton,
+    content_regex_btt: ToggleButton,
+    binary_switch: Switch,
+    hidden_switch: Switch,
+    ignore_files_switch: Switch,
+    follow_links_switch: Switch,
+    same_fs_switch: Switch,
+    min_size_row: EntryRow,
+    max_size_row: EntryRow,
+    // The after and before rows, in the same order as DATE_KINDS
+    date_rows: Vec<(EntryRow, EntryRow)>,
+    owner_row: EntryRow,
+    group_row: EntryRow,
+    perm_mode_row: EntryRow,
+    permission_row: ComboRow,
+    error_label: Label,
+}
+
+const DATE_KINDS: [&str; 3] = ["Modified", "Created", "Accessed"];
+
+// Name and extensions of the categories, in the same order as the category selector
+// after its first item, which is for the extensions written by hand
+fn extension_categories(configs: &setup::YamlConfiguration) -> Vec<(String, String)> {
+    EXTENSION_CATEGORIES.iter()
+        .map(|(name, exts)| (String::from(*name), String::from(*exts)))
+        .chain(configs.general.categories.iter().map(|category| (category.name.clone(), category.extensions.clone())))
+        .collect()
+}
+
+// Adds the category to the config, or replaces the one with the same name
+fn save_category(name: &str, extensions: &str) {
+    info!("main::save_category: Saving the extension category {}...", name);
+    let category = setup::ExtensionCategory { name: String::from(name), extensions: String::from(extensions) };
+    setup::update_conf(|configs| match configs.general.categories.iter_mut().find(|saved| saved.name == name) {
+        Some(saved) => *saved = category,
+        None => configs.general.categories.push(category),
+    });
+}
+
+// An empty row gives None, the row is returned with the error to mark it
+fn optional_input<T>(row: &EntryRow, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, (EntryRow, String)> {
+    let text = row.text();
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    parse(&text).map(Some).map_err(|err| (row.clone(), err))
+}
+
+impl SearchForm {
+    fn match_mode(&self) -> MatchMode {
+        MatchMode::from_index(self.mode_row.selected())
+    }
+
+    fn report(&self) -> Report {
+        match Report::from_index(self.report_row.selected()) {
+            Report::Empty(_) => Report::Empty(self.nested_empty_switch.is_active()),
+            report => report,
+        }
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        filters::split_patterns(&self.path_row.text()).into_iter().map(PathBuf::from).collect()
+    }
+
+    // The rows that can have an invalid input
+    fn validated_rows(&self) -> Vec<EntryRow> {
+        let mut rows = vec![
+            self.pattern_row.clone(),
+            self.content_row.clone(),
+            self.exclude_row.clone(),
+            self.min_depth_row.clone(),
+            self.max_depth_row.clone(),
+            self.min_size_row.clone(),
+            self.max_size_row.clone(),
+        ];
+        for (after_row, before_row) in &self.date_rows {
+            rows.push(after_row.clone());
+            rows.push(before_row.clone());
+        }
+        rows.push(self.owner_row.clone());
+        rows.push(self.group_row.clone());
+        rows.push(self.perm_mode_row.clone());
+        rows
+    }
+
+    // On error, also gives the row that has the invalid input
+    fn parse(&self) -> Result<(NameMatcher, FinderOptions), (EntryRow, String)> {
+        let matcher = if self.report().uses_matcher() {
+            NameMatcher::new(self.match_mode(), &self.pattern_row.text(), self.case_btt.is_active())
+                .map_err(|err| (self.pattern_row.clone(), err))?
+        } else {
+            NameMatcher::All
+        };
+
+        let content_text = self.content_row.text();
+        let content = if content_text.is_empty() {
+            None
+        } else {
+            let max_size = self.configs.general.content_search_max_mb * 1024 * 1024;
+            let query = ContentQuery::new(&content_text, self.content_regex_btt.is_active(), self.binary_switch.is_active(), max_size)
+                .map_err(|err| (self.content_row.clone(), err))?;
+            Some(query)
+        };
+
+        // The ones of the config go first, so an error points to the field
+        let mut exclude = self.configs.general.exclude.clone();
+        exclude.extend(filters::split_patterns(&self.exclude_row.text()));
+        // Only checks the patterns, the finder builds the excludes of every root
+        filters::build_excludes(Path::new(""), &exclude)
+            .map_err(|err| (self.exclude_row.clone(), err))?;
+
+        let depth = DepthRange::new(
+            optional_input(&self.min_depth_row, filters::parse_depth)?,
+            optional_input(&self.max_depth_row, filters::parse_depth)?,
+        ).map_err(|err| (self.min_depth_row.clone(), err))?;
+
+        let size = SizeRange::new(
+            optional_input(&self.min_size_row, filters::parse_size)?,
+            optional_input(&self.max_size_row, filters::parse_size)?,
+        ).map_err(|err| (self.min_size_row.clone(), err))?;
+
+        let mut date_ranges = vec![];
+        for (after_row, before_row) in &self.date_rows {
+            let range = DateRange::new(
+                optional_input(after_row, filters::parse_date)?,
+                optional_input(before_row, filters::parse_date)?,
+            ).map_err(|err| (after_row.clone(), err))?;
+            date_ranges.push(range);
+        }
+        let dates = DateFilters { modified: date_ranges[0], created: date_ranges[1], accessed: date_ranges[2] };
+
+        let permissions = PermissionFilters::new(
+            optional_input(&self.owner_row, filters::parse_owner)?,
+            optional_input(&self.group_row, filters::parse_group)?,
+            optional_input(&self.perm_mode_row, filters::parse_mode)?,
+            PermissionCheck::from_index(self.permission_row.selected()),
+        );
+
+        Ok((matcher, FinderOptions {
+            threads: self.configs.general.threads,
+            content,
+            size,
+            dates,
+            entry_type: TypeFilter::from_index(self.type_row.selected()),
+            permissions,
+            depth,
+            include_hidden: self.hidden_switch.is_active(),
+            respect_ignore_files: self.ignore_files_switch.is_active(),
+            follow_links: self.follow_links_switch.is_active(),
+            same_file_system: self.same_fs_switch.is_active(),
+            detect_types: self.configs.general.detect_file_types,
+            custom_ignore_file: setup::ignore_file(),
+            exclude,
+        }))
+    }
+
+    // Compiles the patterns, if one is invalid its row is marked and the error is shown under the inputs
+    fn read(&self) -> Result<(NameMatcher, FinderOptions), String> {
+        for row in self.validated_rows() {
+            row.remove_css_class("error");
+        }
+        match self.parse() {
+            Ok(read) => {
+                self.error_label.set_visible(false);
+                Ok(read)
+            }
+            Err((row, err)) => {
+                row.add_css_class("error");
+                self.error_label.set_text(&err);
+                self.error_label.set_visible(true);
+                Err(err)
+            }
+        }
+    }
+}
+
+fn format_progress(progress: &FinderProgress) -> String {
+    let text = format!(
+        "{} entries seen in {} directories, {} matches, {:.1} s",
+        progress.entries_seen,
+        progress.dirs_visited,
+        progress.matches,
+        progress.elapsed.as_secs_f64()
+    );
+    if progress.loops > 0 {
+        format!("{}, {} symlink loops skipped", text, progress.loops)
+    } else {
+        text
+    }
 }
 
 fn append_text_column(tree: &gtk::TreeView, title: &str, col: i32) {
@@ -38,14 +307,15 @@ fn append_text_column(tree: &gtk::TreeView, title: &str, col: i32) {
 #[derive(Clone)]
 struct ProgressAnimate {
     should_exit: Arc<AtomicBool>,
+    cancel: Arc<AtomicBool>,
     sender: Sender<SendTypes>,
 }
 impl ProgressAnimate {
-    fn new(sender: Sender<SendTypes>) -> Self {
-        ProgressAnimate { should_exit: Arc::new(AtomicBool::new(false)), sender: sender }
+    fn new(sender: Sender<SendTypes>, cancel: Arc<AtomicBool>) -> Self {
+        ProgressAnimate { should_exit: Arc::new(AtomicBool::new(false)), cancel, sender: sender }
     }
 
-    fn animate_progress(&mut self, path: String, extensions: String) {
+    fn animate_progress(&mut self, roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions, report: Report) {
         let self_clone = self.clone();
         
         let _find_thread = {
@@ -68,8 +338,19 @@ impl ProgressAnimate {
                 }
             });
         };
-        let files = find(path, extensions);
-        if let Err(err) = files.clone() {
+        let files = match report {
+            Report::Files => find(roots, matcher, options, self.sender.clone(), &self.cancel),
+            Report::Duplicates => find_duplicates(roots, matcher, options, self.sender.clone(), &self.cancel),
+            Report::Empty(nested_dirs) => find_empty(roots, matcher, options, nested_dirs, self.sender.clone(), &self.cancel),
+            Report::DiskUsage => find_disk_usage(roots, matcher, options, self.sender.clone(), &self.cancel),
+        };
+        if self.cancel.load(Ordering::Relaxed) {
+            self.should_exit.store(true, Ordering::Relaxed);
+            self.sender.send(SendTypes::Cancelled(files.unwrap_or(0))).unwrap_or_else(|err| {
+                error!("main::ProgressAnimate: Error while sending continue signal to the main thread!. Error: {}", err);
+                info!("main::ProgressAnimate: Create a new issue in the github page");
+            });
+        } else if let Err(err) = files.clone() {
             self.should_exit.store(true, Ordering::Relaxed);
             self.sender.send(SendTypes::Error(err)).unwrap_or_else(|err| {
                 error!("main::ProgressAnimate: Error while sending continue signal to the main thread!. Error: {}", err);
@@ -78,7 +359,7 @@ impl ProgressAnimate {
             ()
         } else {
             self.should_exit.store(true, Ordering::Relaxed);
-            self.sender.send(SendTypes::VectorValue(files.unwrap())).unwrap_or_else(|err| {
+            self.sender.send(SendTypes::Finished(files.unwrap())).unwrap_or_else(|err| {
                 error!("main::ProgressAnimate: Error while sending continue signal to the main thread!. Error: {}", err);
                 info!("main::ProgressAnimate: Create a new issue in the github page");
             });
@@ -87,43 +368,202 @@ impl ProgressAnimate {
     }
 }
 
-fn find(path: String, extensions: String) -> Result<Vec<PathBuf>, String> {
-    let mut find_obj = Finder::new(path, extensions);
-    if let Err(_) = find_obj.find() {
-        Err(String::from("No files founded in the specified dir"))
-    } else {
-        Ok(find_obj.get_all())
+fn find(roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions, sender: Sender<SendTypes>, cancel: &AtomicBool) -> Result<usize, String> {
+    // The results page gets the matches from the events, the finder only counts them
+    let mut find_obj = Finder::new(roots, matcher, options).without_results();
+    let result = find_obj.find(cancel, move |event| match event {
+        FinderEvent::Matches(files) => {
+            sender.send(SendTypes::VectorValue(files)).unwrap_or_else(|err| {
+                error!("main::find: Error while sending the found files to the main thread!. Error: {}", err);
+            });
+        }
+        FinderEvent::Progress(progress) => {
+            sender.send(SendTypes::Progress(progress)).unwrap_or_else(|err| {
+                error!("main::find: Error while sending the progress to the main thread!. Error: {}", err);
+            });
+        }
+    });
+    result.map_err(|_| String::from("No files founded in the specified dir"))
+}
+
+// Walks like find, but only the progress is sent, the files found are used to make a report
+fn find_for_report(roots: Vec<PathBuf>, matcher: NameMatcher, mut options: FinderOptions, sender: Sender<SendTypes>, cancel: &AtomicBool) -> Result<Finder, String> {
+    // The types aren't shown in the reports
+    options.detect_types = false;
+    let mut find_obj = Finder::new(roots, matcher, options);
+    let result = find_obj.find(cancel, move |event| {
+        if let FinderEvent::Progress(progress) = event {
+            sender.send(SendTypes::Progress(progress)).unwrap_or_else(|err| {
+                error!("main::find_for_report: Error while sending the progress to the main thread!. Error: {}", err);
+            });
+        }
+    });
+    if result.is_err() {
+        return Err(String::from("No files founded in the specified dir"));
+    }
+    Ok(find_obj)
+}
+
+fn find_duplicates(roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions, sender: Sender<SendTypes>, cancel: &AtomicBool) -> Result<usize, String> {
+    let files = find_for_report(roots, matcher, options, sender.clone(), cancel)?.get_all();
+    let status_sender = sender.clone();
+    let groups = duplicates::find_duplicates(&files, cancel, move |hashed, total| {
+        let status = format!("Comparing the files with the same size, {} of {}", hashed, total);
+        status_sender.send(SendTypes::Status(status)).unwrap_or_else(|err| {
+            error!("main::find_duplicates: Error while sending the status to the main thread!. Error: {}", err);
+        });
+    });
+    if groups.is_empty() && !cancel.load(Ordering::Relaxed) {
+        return Err(String::from("No duplicated files founded in the specified dir"));
     }
+    let total = groups.len();
+    sender.send(SendTypes::Duplicates(groups)).unwrap_or_else(|err| {
+        error!("main::find_duplicates: Error while sending the duplicates to the main thread!. Error: {}", err);
+    });
+    Ok(total)
+}
+
+fn find_empty(roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions, nested_dirs: bool, sender: Sender<SendTypes>, cancel: &AtomicBool) -> Result<usize, String> {
+    // The size, content, date, permission and type filters would hide the entries
+    // the report looks for, see Report::hint
+    let options = FinderOptions {
+        threads: options.threads,
+        depth: options.depth,
+        include_hidden: options.include_hidden,
+        respect_ignore_files: options.respect_ignore_files,
+        custom_ignore_file: options.custom_ignore_file,
+        follow_links: options.follow_links,
+        same_file_system: options.same_file_system,
+        exclude: options.exclude,
+        ..Default::default()
+    };
+    let found = find_for_report(roots, matcher, options, sender.clone(), cancel)?.get_all();
+    sender.send(SendTypes::Status(String::from("Looking for the empty entries"))).unwrap_or_else(|err| {
+        error!("main::find_empty: Error while sending the status to the main thread!. Error: {}", err);
+    });
+    let report = EmptyReport::new(&found, nested_dirs);
+    if report.is_empty() && !cancel.load(Ordering::Relaxed) {
+        return Err(String::from("No empty files, empty directories or broken symlinks founded in the specified dir"));
+    }
+    let total = report.len();
+    sender.send(SendTypes::Empty(report)).unwrap_or_else(|err| {
+        error!("main::find_empty: Error while sending the report to the main thread!. Error: {}", err);
+    });
+    Ok(total)
+}
+
+fn find_disk_usage(roots: Vec<PathBuf>, matcher: NameMatcher, options: FinderOptions, sender: Sender<SendTypes>, cancel: &AtomicBool) -> Result<usize, String> {
+    // The filters would leave files out of the totals, see Report::hint
+    let options = FinderOptions {
+        threads: options.threads,
+        include_hidden: true,
+        follow_links: options.follow_links,
+        same_file_system: options.same_file_system,
+        exclude: options.exclude,
+        ..Default::default()
+    };
+    let find_obj = find_for_report(roots, matcher, options, sender.clone(), cancel)?;
+    let found = find_obj.get_all();
+    if found.is_empty() && !cancel.load(Ordering::Relaxed) {
+        return Err(String::from("No files founded in the specified dir"));
+    }
+    sender.send(SendTypes::Status(String::from("Adding up the sizes of the directories"))).unwrap_or_else(|err| {
+        error!("main::find_disk_usage: Error while sending the status to the main thread!. Error: {}", err);
+    });
+    let usage = du::disk_usage(find_obj.roots(), &found);
+    let total = usage.iter().map(|dir| dir.count()).sum();
+    sender.send(SendTypes::DiskUsage(usage)).unwrap_or_else(|err| {
+        error!("main::find_disk_usage: Error while sending the disk usage to the main thread!. Error: {}", err);
+    });
+    Ok(total)
 }
 
 impl Callbacks {
-    fn new(gtk_box: GtkBox, path: String, exts: String, nb: Notebook) -> Self {
-        Callbacks { gtk_box: gtk_box, path: path, exts: exts, nb: nb}
+    fn new(gtk_box: GtkBox, roots: Vec<PathBuf>, matcher: NameMatcher, nb: Notebook, options: FinderOptions, report: Report) -> Self {
+        Callbacks { gtk_box: gtk_box, roots, matcher, nb: nb, options, report }
     }
 
 
     fn find_btt_callback(&self, transient_for: ApplicationWindow) {
-        let prog = ProgressBar::new();
-        self.gtk_box.append(&prog);
+        let prog = ProgressBar::builder()
+            .hexpand(true)
+            .valign(gtk::Align::Center)
+            .build();
+        let cancel_btt = Button::builder()
+            .label("Cancel")
+            .build();
+        let bar_box = GtkBox::builder()
+            .spacing(10)
+            .build();
+        bar_box.append(&prog);
+        bar_box.append(&cancel_btt);
+
+        let stats_label = Label::builder()
+            .halign(gtk::Align::Start)
+            .css_classes(vec![String::from("caption")])
+            .build();
+        let dir_label = Label::builder()
+            .halign(gtk::Align::Start)
+            .ellipsize(gtk::pango::EllipsizeMode::Middle)
+            .css_classes(vec![String::from("caption"), String::from("dim-label")])
+            .build();
+
+        let prog_box = GtkBox::builder()
+            .margin_end(20)
+            .margin_start(20)
+            .margin_bottom(10)
+            .spacing(4)
+            .orientation(Orientation::Vertical)
+            .build();
+        prog_box.append(&bar_box);
+        prog_box.append(&stats_label);
+        prog_box.append(&dir_label);
+        self.gtk_box.append(&prog_box);
         info!("main::Callbacks::find_bt_callback: Creating glib channel...");
         let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
         prog.pulse();
 
-        let mut prog_cb = ProgressAnimate::new(sender.clone());
-        let path = self.path.clone();
-        let exts = self.exts.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_clone = Arc::clone(&cancel);
+        cancel_btt.connect_clicked(move |btt| {
+            info!("main::Callbacks::find_bt_callback: Cancelling search...");
+            cancel_clone.store(true, Ordering::Relaxed);
+            btt.set_sensitive(false);
+        });
+
+        let mut prog_cb = ProgressAnimate::new(sender.clone(), Arc::clone(&cancel));
+        let roots = self.roots.clone();
+        let matcher = self.matcher.clone();
+        let options = self.options.clone();
+        let report = self.report;
 
         info!("main::Callbacks::find_bt_callback: Finding files in another thread...");
         // Inicia el hilo secundario que actualiza el widget
         thread::spawn(move || {
-            prog_cb.animate_progress(path, exts);
+            prog_cb.animate_progress(roots, matcher, options, report);
         });
 
+        // The Detected type mode keeps the types it read, even when they aren't detected for every file
+        let show_mime = self.options.detect_types || matches!(self.matcher, NameMatcher::Mime(_));
+        let (nb_box, stated_obj, results_label) = build_page2(self.nb.clone(), Arc::clone(&cancel), self.options.content.is_some(), self.roots.len() > 1, show_mime, self.report);
+        // The batches come in the order the walker threads find them
+        if matches!(self.matcher, NameMatcher::Fuzzy(_)) {
+            stated_obj.sort_by_score();
+        } else {
+            stated_obj.sort_by_path();
+        }
+        let placeholder_label = Label::builder()
+            .use_markup(true)
+            .label("<b>Input set</b>")
+            .build();
+
+        self.nb.append_page(&nb_box, Some(&placeholder_label));
+        self.nb.next_page();
+
         let box_clone = self.gtk_box.clone();
-        let prog_clone = prog.clone();
+        let prog_clone = prog_box.clone();
 
         let nb_clone = self.nb.clone();
-        let _self_clone = self.clone();
         let tf = transient_for.clone();
         receiver.attach(None, move |msg| {
 
@@ -133,16 +573,71 @@ impl Callbacks {
                     prog.pulse();
                     glib::Continue(true)
                 }
+                SendTypes::Status(status) => {
+                    dir_label.set_text(&status);
+                    glib::Continue(true)
+                }
+                SendTypes::Duplicates(groups) => {
+                    info!("main::Callbacks::find_bt_callback: Recivied {} groups of duplicated files", groups.len());
+                    let wasted: u64 = groups.iter().map(|group| group.wasted()).sum();
+                    results_label.set_markup(&format!(
+                        "<b>Results</b> ({} groups, {} wasted)",
+                        groups.len(),
+                        stated::convert_bytes_to_human_readable(wasted)
+                    ));
+                    stated_obj.insert_duplicates(&groups);
+                    glib::Continue(true)
+                }
+                SendTypes::Empty(report) => {
+                    info!("main::Callbacks::find_bt_callback: Recivied {} empty entries", report.len());
+                    results_label.set_markup(&format!("<b>Results</b> ({} entries)", report.len()));
+                    stated_obj.insert_empty_report(&report);
+                    glib::Continue(true)
+                }
+                SendTypes::DiskUsage(usage) => {
+                    let total: u64 = usage.iter().map(|dir| dir.apparent).sum();
+                    info!("main::Callbacks::find_bt_callback: Recivied the disk usage of {} roots", usage.len());
+                    results_label.set_markup(&format!("<b>Results</b> ({} in total)", stated::convert_bytes_to_human_readable(total)));
+                    stated_obj.insert_disk_usage(&usage);
+                    glib::Continue(true)
+                }
+                SendTypes::Progress(progress) => {
+                    stats_label.set_text(&format_progress(&progress));
+                    dir_label.set_text(&progress.current_dir.to_string_lossy());
+                    glib::Continue(true)
+                }
                 SendTypes::VectorValue(vec_files) => {
-                    info!("main::Callbacks::find_bt_callback: Recivied list of files from another thread");
-                    if let Ok(nb_box) = build_page2(vec_files, nb_clone.clone(), tf.clone()) {
-                        let placeholder_label = Label::builder()
-                            .use_markup(true)
-                            .label("<b>Input set</b>")
+                    info!("main::Callbacks::find_bt_callback: Recivied {} files from another thread", vec_files.len());
+                    if let Err(err) = stated_obj.stat_and_insert(vec_files) {
+                        let msg_diag = adw::MessageDialog::new(Some(&tf), Some("Failed"), Some(&err));
+                        msg_diag.add_response("Ok", "Ok");
+                        msg_diag.present();
+                        cancel.store(true, Ordering::Relaxed);
+                        close_page(&nb_clone, &nb_box);
+                        box_clone.remove(&prog_clone);
+                        return glib::Continue(false);
+                    }
+                    glib::Continue(true)
+                }
+                SendTypes::Finished(total) => {
+                    info!("main::Callbacks::find_bt_callback: Search finished, {} files found", total);
+                    box_clone.remove(&prog_clone);
+                    glib::Continue(false)
+                }
+                SendTypes::Cancelled(total) => {
+                    info!("main::Callbacks::find_bt_callback: Search cancelled, {} files found", total);
+                    // Nothing to say when the user already left the results page
+                    if total == 0 && nb_clone.page_num(&nb_box).is_some() {
+                        let msg_diag = adw::MessageDialog::builder()
+                            .heading("Search cancelled")
+                            .body("The search was stopped before finding anything")
+                            .transient_for(&tf.clone())
                             .build();
-
-                        nb_clone.append_page(&nb_box, Some(&placeholder_label));
-                        nb_clone.next_page();
+                        msg_diag.add_response("Ok", "Ok");
+                        msg_diag.present();
+                        close_page(&nb_clone, &nb_box);
+                    } else {
+                        results_label.set_markup("<b>Results</b> (search cancelled)");
                     }
                     box_clone.remove(&prog_clone);
                     glib::Continue(false)
@@ -156,6 +651,7 @@ impl Callbacks {
                         .build();
                     msg_diag.add_response("Ok", "Ok");
                     msg_diag.present();
+                    close_page(&nb_clone, &nb_box);
                     box_clone.remove(&prog_clone);
                     glib::Continue(false)
                 }
@@ -164,7 +660,67 @@ impl Callbacks {
     }
 }
 
-fn build_page2(files: Vec<PathBuf>, nb: Notebook, transient_for: ApplicationWindow) -> Result<GtkBox, ()> {
+// Removes a results page, if the user didn't close it already
+fn close_page(nb: &Notebook, page: &GtkBox) {
+    if let Some(num) = nb.page_num(page) {
+        nb.set_current_page(Some(0));
+        nb.remove_page(Some(num));
+    }
+}
+
+// Shows what is inside the directory at `root` of the disk usage tree, the whole tree with None
+fn show_usage_dir(treeview: &TreeView, store: &gtk::TreeStore, root: Option<&gtk::TreePath>, up_button: &Button) {
+    match root {
+        Some(root) => treeview.set_model(Some(&gtk::TreeModelFilter::new(store, Some(root)))),
+        None => treeview.set_model(Some(store)),
+    }
+    let dir = root.and_then(|root| store.iter(root)).map(|iter| store.get::<String>(&iter, 1));
+    up_button.set_sensitive(dir.is_some());
+    up_button.set_label(&match dir {
+        Some(dir) => format!("Go up from {}", dir),
+        None => String::from("Go up"),
+    });
+}
+
+// Double clicking a directory of the disk usage tree shows only what is inside
+// it, the returned button goes back to its parent
+fn build_drill_down(treeview: &TreeView, store: &gtk::TreeStore) -> Button {
+    let up_button = Button::builder()
+        .label("Go up")
+        .sensitive(false)
+        .halign(gtk::Align::Start)
+        .css_classes(vec![String::from("flat")])
+        .build();
+    // Path in the store of the directory shown, None when showing the roots
+    let shown_dir: Rc<RefCell<Option<gtk::TreePath>>> = Rc::new(RefCell::new(None));
+
+    let store_clone = store.clone();
+    let shown_dir_clone = shown_dir.clone();
+    let up_button_clone = up_button.clone();
+    treeview.connect_row_activated(move |treeview, path, _| {
+        let store_path = match treeview.model().and_downcast::<gtk::TreeModelFilter>() {
+            Some(filter) => filter.convert_path_to_child_path(path),
+            None => Some(path.clone()),
+        };
+        let Some(store_path) = store_path else { return };
+        if store_clone.iter(&store_path).is_some_and(|iter| store_clone.iter_has_child(&iter)) {
+            show_usage_dir(treeview, &store_clone, Some(&store_path), &up_button_clone);
+            shown_dir_clone.replace(Some(store_path));
+        }
+    });
+
+    let treeview = treeview.clone();
+    let store = store.clone();
+    up_button.connect_clicked(move |up_button| {
+        let parent = shown_dir.take().and_then(|mut dir| (dir.up() && dir.depth() > 0).then_some(dir));
+        show_usage_dir(&treeview, &store, parent.as_ref(), up_button);
+        shown_dir.replace(parent);
+    });
+    up_button
+}
+
+// The page starts empty, the files are inserted in the returned Stated while the search runs
+fn build_page2(nb: Notebook, cancel: Arc<AtomicBool>, show_content: bool, show_root: bool, show_mime: bool, report: Report) -> (GtkBox, stated::Stated, Label) {
     let config = setup::load_conf();
 
     let scrolled_window = gtk::ScrolledWindow::builder()
@@ -191,32 +747,77 @@ fn build_page2(files: Vec<PathBuf>, nb: Notebook, transient_for: ApplicationWind
         .label("Return to the main page")
         .build();
 
+    // Nobody would see the rest of the results
     return_button.connect_clicked(move |_| {
+        cancel.store(true, Ordering::Relaxed);
         nb.prev_page();
         nb.remove_page(Some(1));
     });
 
     let stated_obj = stated::Stated::new(config);
 
-    if let Err(err) = stated_obj.stat_and_insert(files) {
-        
-        let msg_diag = adw::MessageDialog::new(Some(&transient_for), Some("Failed"), Some(&err));
-        msg_diag.add_response("Ok", "Ok");
-        msg_diag.present();
-        return Err(());
-    };
-
-    let list_store = stated_obj.get_liststore();
     let treeview = TreeView::builder()
-        .model(&list_store)
         .vexpand(true)
         .build();
 
-    let cols = vec!["Name", "Extension", "Path", "Total Size", "Date of modification"];
+    // The reports group the files in a tree
+    let report_cols = match report {
+        Report::Files => None,
+        Report::Duplicates => Some(vec![("Name", 0), ("Path", 1), ("Size", 2), ("Wasted space", 3)]),
+        Report::Empty(_) => Some(vec![("Name", 0), ("Path", 1), ("Points to", 3)]),
+        Report::DiskUsage => Some(vec![("Name", 0), ("Size", 2), ("Size on disk", 3), ("Percentage", 4), ("Path", 1)]),
+    };
+    if let Some(cols) = report_cols {
+        treeview.set_model(Some(&stated_obj.get_treestore()));
+        for (title, col) in cols {
+            append_text_column(&treeview, title, col);
+        }
+        page2_box.append(&label);
+        if report == Report::DiskUsage {
+            page2_box.append(&build_drill_down(&treeview, &stated_obj.get_treestore()));
+        } else {
+            // Double clicking a group opens or closes it
+            treeview.connect_row_activated(|treeview, path, _| {
+                if treeview.row_expanded(path) {
+                    treeview.collapse_row(path);
+                } else {
+                    treeview.expand_row(path, false);
+                }
+            });
+        }
+        scrolled_window.set_child(Some(&treeview));
+        page2_box.append(&scrolled_window);
+        page2_box.append(&return_button);
+        return (page2_box, stated_obj, label);
+    }
+    treeview.set_model(Some(&stated_obj.get_liststore()));
+
+    // Title and column of the list store
+    let mut cols = vec![
+        ("Name", 0),
+        ("Type", 10),
+        ("Extension", 1),
+        ("Path", 2),
+        ("Total Size", 3),
+        ("Date of modification", 4),
+        ("Owner", 11),
+        ("Group", 12),
+        ("Permissions", 13),
+    ];
+    if show_mime {
+        cols.insert(3, ("Detected type", 9));
+    }
 
-    for (pos, col) in cols.iter().enumerate() {
-        append_text_column(&treeview, col, pos as i32);
+    for (title, col) in cols {
+        append_text_column(&treeview, title, col);
     };
+    if show_content {
+        append_text_column(&treeview, "Line", 6);
+        append_text_column(&treeview, "Snippet", 7);
+    }
+    if show_root {
+        append_text_column(&treeview, "Root", 8);
+    }
 
     scrolled_window.set_child(Some(&treeview));
 
@@ -224,7 +825,7 @@ fn build_page2(files: Vec<PathBuf>, nb: Notebook, transient_for: ApplicationWind
     page2_box.append(&scrolled_window);
     page2_box.append(&return_button);
 
-    return Ok(page2_box);
+    return (page2_box, stated_obj, label);
 }
 
 fn main() {
@@ -319,23 +920,255 @@ fn main() {
                 .css_classes(vec![String::from("content")])
                 .build();
 
-            // let exts_row = ActionRow::builder()
-            //     .activatable(true)
-            //     .selectable(false)
-            //     .title("Extensions")
-            //     .subtitle("Choose from a list the ext category, or create new category by writing and separating the extensions with this character |")
-            //     .build();
+            let mode_names: Vec<&str> = MatchMode::ALL.iter().map(|mode| mode.label()).collect();
+            let mode_row = ComboRow::builder()
+                .selectable(false)
+                .title("Match mode")
+                .model(&gtk::StringList::new(&mode_names))
+                .build();
+
+            let report_names: Vec<&str> = Report::ALL.iter().map(|report| report.label()).collect();
+            let report_row = ComboRow::builder()
+                .selectable(false)
+                .title("Search for")
+                .subtitle(Report::Files.hint())
+                .model(&gtk::StringList::new(&report_names))
+                .build();
+
+            let nested_empty_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .build();
+            let nested_empty_row = ActionRow::builder()
+                .title("Count nested empty directories")
+                .subtitle("A directory that only holds empty directories is empty too")
+                .activatable_widget(&nested_empty_switch)
+                .visible(false)
+                .build();
+            nested_empty_row.add_suffix(&nested_empty_switch);
+
             let exts_entry_row = EntryRow::builder()
                 .activatable(true)
                 .selectable(false)
-                .title("Extensions (Separated by | )")
+                .title(MatchMode::Extensions.hint())
+                .build();
+            let case_btt = ToggleButton::builder()
+                .label("Aa")
+                .tooltip_text("Match the case of the extensions")
+                .valign(gtk::Align::Center)
+                .css_classes(vec![String::from("flat")])
                 .build();
+            exts_entry_row.add_suffix(&case_btt);
 
             let directory_entry_row = EntryRow::builder()
                 .activatable(true)
                 .selectable(false)
-                .title("Find path")
+                .title("Find paths (Separated by | )")
+                .build();
+
+            let content_regex_btt = ToggleButton::builder()
+                .label(".*")
+                .tooltip_text("Use a regular expression")
+                .valign(gtk::Align::Center)
+                .css_classes(vec![String::from("flat")])
+                .build();
+            let content_entry_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Containing text")
+                .build();
+            content_entry_row.add_suffix(&content_regex_btt);
+
+            let binary_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .build();
+            let binary_row = ActionRow::builder()
+                .title("Search binary files")
+                .activatable_widget(&binary_switch)
+                .build();
+            binary_row.add_suffix(&binary_switch);
+
+            let content_expander = ExpanderRow::builder()
+                .selectable(false)
+                .title("Search inside files")
+                .subtitle("Only the files that contain this text are shown")
+                .build();
+            content_expander.add_row(&content_entry_row);
+            content_expander.add_row(&binary_row);
+
+            let min_size_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Minimum size (like 500k or 10M)")
+                .build();
+            let max_size_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Maximum size (like 2G)")
+                .build();
+
+            let configs = setup::load_conf();
+
+            let category_model = gtk::StringList::new(&["Written by hand"]);
+            for (name, _) in extension_categories(&configs) {
+                category_model.append(&name);
+            }
+            let save_category_btt = Button::builder()
+                .icon_name("document-save-symbolic")
+                .tooltip_text("Save the extensions as a new category")
+                .valign(gtk::Align::Center)
+                .sensitive(false)
+                .css_classes(vec![String::from("flat")])
+                .build();
+            let category_row = ComboRow::builder()
+                .selectable(false)
+                .title("Extension category")
+                .subtitle("Choose a category, or save the extensions written below as a new one")
+                .model(&category_model)
+                .build();
+            category_row.add_suffix(&save_category_btt);
+
+            let hidden_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .active(configs.general.include_hidden)
+                .build();
+            let hidden_row = ActionRow::builder()
+                .title("Include hidden files")
+                .subtitle("Also search inside hidden directories like .git or .cache")
+                .activatable_widget(&hidden_switch)
+                .build();
+            hidden_row.add_suffix(&hidden_switch);
+
+            hidden_switch.connect_active_notify(|switch| {
+                info!("Application::connect_activate: Saving hidden files preference...");
+                setup::update_conf(|configs| configs.general.include_hidden = switch.is_active());
+            });
+
+            let ignore_files_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .active(configs.general.respect_ignore_files)
+                .build();
+            let ignore_files_row = ActionRow::builder()
+                .title("Respect ignore files")
+                .subtitle("Skip what .gitignore, .ignore and .fdignore files ignore, like target or node_modules")
+                .activatable_widget(&ignore_files_switch)
+                .build();
+            ignore_files_row.add_suffix(&ignore_files_switch);
+
+            ignore_files_switch.connect_active_notify(|switch| {
+                info!("Application::connect_activate: Saving ignore files preference...");
+                setup::update_conf(|configs| configs.general.respect_ignore_files = switch.is_active());
+            });
+
+            let follow_links_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .active(configs.general.follow_links)
+                .build();
+            let follow_links_row = ActionRow::builder()
+                .title("Follow symlinks")
+                .subtitle("Also search inside the directories that symlinks point to, loops are skipped")
+                .activatable_widget(&follow_links_switch)
+                .build();
+            follow_links_row.add_suffix(&follow_links_switch);
+
+            follow_links_switch.connect_active_notify(|switch| {
+                info!("Application::connect_activate: Saving follow symlinks preference...");
+                setup::update_conf(|configs| configs.general.follow_links = switch.is_active());
+            });
+
+            let same_fs_switch = Switch::builder()
+                .valign(gtk::Align::Center)
+                .active(configs.general.same_file_system)
+                .build();
+            let same_fs_row = ActionRow::builder()
+                .title("Stay on one filesystem")
+                .subtitle("Don't enter other mounted filesystems, like /proc or network drives")
+                .activatable_widget(&same_fs_switch)
+                .build();
+            same_fs_row.add_suffix(&same_fs_switch);
+
+            same_fs_switch.connect_active_notify(|switch| {
+                info!("Application::connect_activate: Saving same filesystem preference...");
+                setup::update_conf(|configs| configs.general.same_file_system = switch.is_active());
+            });
+
+            let type_names: Vec<&str> = TypeFilter::ALL.iter().map(|filter| filter.label()).collect();
+            let type_row = ComboRow::builder()
+                .selectable(false)
+                .title("Entry type")
+                .model(&gtk::StringList::new(&type_names))
+                .build();
+
+            let exclude_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Exclude (Separated by | , like node_modules or *.min.js)")
+                .build();
+
+            let min_depth_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Minimum depth (1 is directly inside the path)")
+                .build();
+            let max_depth_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Maximum depth")
+                .build();
+
+            let filters_expander = ExpanderRow::builder()
+                .selectable(false)
+                .title("Filters")
+                .subtitle("Leave a field empty to not filter by it")
+                .build();
+            filters_expander.add_row(&type_row);
+            filters_expander.add_row(&exclude_row);
+            filters_expander.add_row(&min_depth_row);
+            filters_expander.add_row(&max_depth_row);
+            filters_expander.add_row(&min_size_row);
+            filters_expander.add_row(&max_size_row);
+
+            let mut date_rows = vec![];
+            for kind in DATE_KINDS {
+                let after_row = EntryRow::builder()
+                    .activatable(true)
+                    .selectable(false)
+                    .title(format!("{} after (like 2024-01-31, 7d or last month)", kind))
+                    .build();
+                let before_row = EntryRow::builder()
+                    .activatable(true)
+                    .selectable(false)
+                    .title(format!("{} before", kind))
+                    .build();
+                filters_expander.add_row(&after_row);
+                filters_expander.add_row(&before_row);
+                date_rows.push((after_row, before_row));
+            }
+
+            let owner_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Owner (user name or id)")
+                .build();
+            let group_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Group (name or id)")
+                .build();
+            let perm_mode_row = EntryRow::builder()
+                .activatable(true)
+                .selectable(false)
+                .title("Exact mode (octal, like 755 or 0644)")
+                .build();
+            let permission_names: Vec<&str> = PermissionCheck::ALL.iter().map(|check| check.label()).collect();
+            let permission_row = ComboRow::builder()
+                .selectable(false)
+                .title("Permissions")
+                .model(&gtk::StringList::new(&permission_names))
                 .build();
+            filters_expander.add_row(&owner_row);
+            filters_expander.add_row(&group_row);
+            filters_expander.add_row(&perm_mode_row);
+            filters_expander.add_row(&permission_row);
 
             let btt_box = GtkBox::builder()
                 .margin_bottom(26)
@@ -357,24 +1190,185 @@ fn main() {
                 .vexpand_set(true)
                 .build();
 
-            let der_clone = directory_entry_row.clone();
-            let eer = exts_entry_row.clone();
+            let error_label = Label::builder()
+                .halign(gtk::Align::Start)
+                .margin_start(22)
+                .margin_end(22)
+                .wrap(true)
+                .visible(false)
+                .css_classes(vec![String::from("error")])
+                .build();
+
+            let form = SearchForm {
+                configs,
+                mode_row: mode_row.clone(),
+                report_row: report_row.clone(),
+                nested_empty_switch: nested_empty_switch.clone(),
+                pattern_row: exts_entry_row.clone(),
+                path_row: directory_entry_row.clone(),
+                content_row: content_entry_row.clone(),
+                type_row: type_row.clone(),
+                exclude_row: exclude_row.clone(),
+                min_depth_row: min_depth_row.clone(),
+                max_depth_row: max_depth_row.clone(),
+                case_btt: case_btt.clone(),
+                content_regex_btt: content_regex_btt.clone(),
+                binary_switch: binary_switch.clone(),
+                hidden_switch: hidden_switch.clone(),
+                ignore_files_switch: ignore_files_switch.clone(),
+                follow_links_switch: follow_links_switch.clone(),
+                same_fs_switch: same_fs_switch.clone(),
+                min_size_row: min_size_row.clone(),
+                max_size_row: max_size_row.clone(),
+                date_rows,
+                owner_row: owner_row.clone(),
+                group_row: group_row.clone(),
+                perm_mode_row: perm_mode_row.clone(),
+                permission_row: permission_row.clone(),
+                error_label: error_label.clone(),
+            };
+
+            let form_clone = form.clone();
+            let category_row_clone = category_row.clone();
+            mode_row.connect_selected_notify(move |_| {
+                let mode = form_clone.match_mode();
+                category_row_clone.set_visible(mode == MatchMode::Extensions);
+                form_clone.case_btt.set_visible(mode == MatchMode::Extensions);
+                // Nothing to write when listing everything
+                form_clone.pattern_row.set_visible(mode != MatchMode::All);
+                form_clone.pattern_row.set_title(mode.hint());
+                if !form_clone.pattern_row.text().is_empty() || mode == MatchMode::All {
+                    let _ = form_clone.read();
+                }
+            });
+
+            let form_clone = form.clone();
+            let category_row_clone = category_row.clone();
+            let nested_empty_row_clone = nested_empty_row.clone();
+            report_row.connect_selected_notify(move |_| {
+                let report = form_clone.report();
+                form_clone.report_row.set_subtitle(report.hint());
+                nested_empty_row_clone.set_visible(matches!(report, Report::Empty(_)));
+                for row in [form_clone.mode_row.upcast_ref::<gtk::Widget>(), category_row_clone.upcast_ref(), form_clone.pattern_row.upcast_ref()] {
+                    row.set_sensitive(report.uses_matcher());
+                }
+                if !report.uses_matcher() || !form_clone.pattern_row.text().is_empty() {
+                    let _ = form_clone.read();
+                }
+            });
+
+            for row in form.validated_rows() {
+                let form_clone = form.clone();
+                row.connect_changed(move |_| {
+                    let _ = form_clone.read();
+                });
+            }
+
+            let exts_entry_row_clone = exts_entry_row.clone();
+            category_row.connect_selected_notify(move |row| {
+                // The first item is for the extensions written by hand
+                let Some(pos) = row.selected().checked_sub(1) else { return };
+                if let Some((_, exts)) = extension_categories(&setup::load_conf()).get(pos as usize) {
+                    exts_entry_row_clone.set_text(exts);
+                }
+            });
+
+            // Editing the extensions of a category makes them written by hand again
+            let category_row_clone = category_row.clone();
+            let save_category_btt_clone = save_category_btt.clone();
+            exts_entry_row.connect_changed(move |row| {
+                save_category_btt_clone.set_sensitive(!row.text().trim().is_empty());
+                let Some(pos) = category_row_clone.selected().checked_sub(1) else { return };
+                let categories = extension_categories(&setup::load_conf());
+                if categories.get(pos as usize).is_none_or(|(_, exts)| *exts != row.text()) {
+                    category_row_clone.set_selected(0);
+                }
+            });
+
+            let category_row_clone = category_row.clone();
+            let exts_entry_row_clone = exts_entry_row.clone();
+            let app_window_transient = window.clone();
+            save_category_btt.connect_clicked(move |_| {
+                let extensions = exts_entry_row_clone.text().to_string();
+                let name_entry = gtk::Entry::builder()
+                    .placeholder_text("Name")
+                    .activates_default(true)
+                    .build();
+                let msg_diag = adw::MessageDialog::builder()
+                    .heading("New category")
+                    .body(format!("Save {} as a category", extensions))
+                    .transient_for(&app_window_transient)
+                    .extra_child(&name_entry)
+                    .default_response("save")
+                    .close_response("cancel")
+                    .build();
+                msg_diag.add_response("cancel", "Cancel");
+                msg_diag.add_response("save", "Save");
+                msg_diag.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+
+                let category_model = category_model.clone();
+                let category_row = category_row_clone.clone();
+                msg_diag.connect_response(Some("save"), move |_, _| {
+                    let name = name_entry.text().trim().to_string();
+                    if name.is_empty() {
+                        return;
+                    }
+                    save_category(&name, &extensions);
+                    let names: Vec<String> = extension_categories(&setup::load_conf()).into_iter().map(|(name, _)| name).collect();
+                    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+                    category_model.splice(1, category_model.n_items() - 1, &names);
+                    // A saved category goes after a built-in one with the same name
+                    if let Some(pos) = names.iter().rposition(|saved| *saved == name) {
+                        category_row.set_selected(pos as u32 + 1);
+                    }
+                });
+                msg_diag.present();
+            });
+
+            let form_clone = form.clone();
+            content_regex_btt.connect_toggled(move |_| {
+                let _ = form_clone.read();
+            });
+
+            let form_clone = form.clone();
+            case_btt.connect_toggled(move |_| {
+                let _ = form_clone.read();
+            });
+
             let mb = main_box.clone();
             let nb = notebook.clone();
             let app_window_transient = window.clone();
             find_btt.connect_clicked(move |_| {
-                let cbs = Callbacks::new(mb.clone(), der_clone.text().to_string(), eer.text().to_string(), nb.clone());
+                let (matcher, options) = match form.read() {
+                    Ok(read) => read,
+                    Err(err) => {
+                        info!("Application::connect_activate: Invalid input: {}", err);
+                        return;
+                    }
+                };
+                let cbs = Callbacks::new(mb.clone(), form.roots(), matcher, nb.clone(), options, form.report());
                 cbs.find_btt_callback(app_window_transient.clone());
             });
 
             btt_box.append(&browse_path_btt);
             btt_box.append(&find_btt);
             
+            input_list_box.append(&report_row);
+            input_list_box.append(&nested_empty_row);
+            input_list_box.append(&mode_row);
+            input_list_box.append(&category_row);
             input_list_box.append(&exts_entry_row);
             input_list_box.append(&directory_entry_row);
+            input_list_box.append(&hidden_row);
+            input_list_box.append(&ignore_files_row);
+            input_list_box.append(&follow_links_row);
+            input_list_box.append(&same_fs_row);
+            input_list_box.append(&content_expander);
+            input_list_box.append(&filters_expander);
 
             widgets_box.append(&placeholder_label);
             widgets_box.append(&input_list_box);
+            widgets_box.append(&error_label);
             widgets_box.append(&btt_box);
         info!("Application::connect_activate: Success!");
         // ENF OF PAGE 1
@@ -383,7 +1377,14 @@ fn main() {
 
         let placeholder_label = Label::new(Some("Main Page"));
 
-        notebook.append_page(&widgets_box, Some(&placeholder_label));
+        // The inputs don't fit in small windows when the expanders are open
+        let main_page_scroll = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .child(&widgets_box)
+            .build();
+
+        notebook.append_page(&main_page_scroll, Some(&placeholder_label));
 
         // THE START OF THE PAGE 2 IS IN THE CALLBACK STRUCT
         // START OF PAGE 2
@@ -391,7 +1392,7 @@ fn main() {
             // ^
             // |
             // |
-            // PAGE 2 STARTS AT LINE 166 IN THE FUNCTION build_page2()
+            // PAGE 2 STARTS IN THE FUNCTION build_page2()
 
         // END OF PAGE 2
 
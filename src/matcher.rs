@@ -0,0 +1,213 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::filetype::{self, MimeQuery};
+use crate::query::Query;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MatchMode {
+    #[default]
+    Extensions,
+    Glob,
+    Regex,
+    Fuzzy,
+    All,
+    Mime,
+    Query,
+}
+
+// Written in the extension list to match the files without extension, like Makefile
+pub const NO_EXTENSION: &str = "(none)";
+
+impl MatchMode {
+    // Same order as the match mode selector of the main page
+    pub const ALL: [MatchMode; 7] = [MatchMode::Extensions, MatchMode::Glob, MatchMode::Regex, MatchMode::Fuzzy, MatchMode::All, MatchMode::Mime, MatchMode::Query];
+
+    pub fn from_index(index: u32) -> MatchMode {
+        MatchMode::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatchMode::Extensions => "Extension list",
+            MatchMode::Glob => "Glob",
+            MatchMode::Regex => "Regular expression",
+            MatchMode::Fuzzy => "Fuzzy",
+            MatchMode::All => "All files",
+            MatchMode::Mime => "Detected type",
+            MatchMode::Query => "Query",
+        }
+    }
+
+    // Title of the pattern entry
+    pub fn hint(&self) -> &'static str {
+        match self {
+            MatchMode::Extensions => "Extensions (Separated by | , like jpg, tar.gz or (none))",
+            MatchMode::Glob => "Glob pattern (like report_*.xlsx)",
+            MatchMode::Regex => "Regular expression",
+            MatchMode::Fuzzy => "Fuzzy query (fragments of the path)",
+            MatchMode::All => "",
+            MatchMode::Mime => "Types (Separated by | , like image/* or application/pdf)",
+            MatchMode::Query => "Query (like ext:rs,toml size>10k -path:target)",
+        }
+    }
+}
+
+// The built-in categories of the extension selector, the ones saved by the user go after them
+pub const EXTENSION_CATEGORIES: [(&str, &str); 5] = [
+    ("Images", "jpg|jpeg|png|gif|bmp|webp|svg|tif|tiff|ico|heic|raw"),
+    ("Documents", "pdf|doc|docx|odt|rtf|txt|md|xls|xlsx|ods|csv|ppt|pptx|odp|epub"),
+    ("Audio", "mp3|flac|ogg|opus|wav|m4a|aac|wma|aiff"),
+    ("Code", "rs|c|h|cpp|hpp|cs|java|kt|go|py|rb|php|js|ts|lua|swift|sh|html|css|toml|yml|yaml|json"),
+    ("Archives", "zip|tar|gz|tgz|bz2|xz|zst|7z|rar|iso"),
+];
+
+// Only the last line of the error, the rest is a multiline drawing that doesn't fit in a label
+pub fn regex_error(err: regex::Error) -> String {
+    match err {
+        regex::Error::Syntax(msg) => format!("Invalid regex: {}", msg.lines().last().unwrap_or_default().trim_start_matches("error: ")),
+        err => format!("Invalid regex: {}", err),
+    }
+}
+
+/// Scores paths against a query the way fzf does, the characters of the
+/// query must appear in order but not necessarily together
+#[derive(Clone)]
+pub struct FuzzyQuery {
+    query: String,
+    matcher: Arc<SkimMatcherV2>,
+}
+
+impl fmt::Debug for FuzzyQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FuzzyQuery").field("query", &self.query).finish()
+    }
+}
+
+impl FuzzyQuery {
+    pub fn new(query: &str) -> Self {
+        FuzzyQuery { query: String::from(query), matcher: Arc::new(SkimMatcherV2::default()) }
+    }
+
+    /// Higher is better, `None` when the path doesn't match at all
+    pub fn score(&self, path: &Path) -> Option<i64> {
+        let path_score = self.matcher.fuzzy_match(&path.to_string_lossy(), &self.query)?;
+        // Hits in the file name itself rank above the ones spread over the directories
+        let name_score = path.file_name()
+            .and_then(|name| self.matcher.fuzzy_match(&name.to_string_lossy(), &self.query))
+            .unwrap_or(0);
+        Some(path_score + name_score)
+    }
+}
+
+/// A filename pattern, compiled once before the walk starts
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    // The extensions are kept with their leading dot, so `tar.gz` or
+    // `d.ts` can be checked against the end of the file name, an empty one
+    // stands for NO_EXTENSION. The bool is true when the case must match
+    Extensions(Vec<String>, bool),
+    // The bool is true when the glob has a path separator and must be checked
+    // against the path under the search root instead of only the file name
+    Glob(GlobMatcher, bool),
+    Regex(Regex),
+    Fuzzy(FuzzyQuery),
+    // Every entry under the path
+    All,
+    // Reads the start of the files, see `filetype::detect`
+    Mime(MimeQuery),
+    // Boxed, the terms of a query have matchers too
+    Query(Box<Query>),
+}
+
+impl NameMatcher {
+    /// `case_sensitive` is only used by the extension list, written like `rs | .toml | tar.gz`
+    pub fn new(mode: MatchMode, pattern: &str, case_sensitive: bool) -> Result<Self, String> {
+        match mode {
+            MatchMode::Extensions => {
+                let exts_vec: Vec<String> = pattern.split("|")
+                    .map(|ext| ext.trim())
+                    .filter(|ext| !ext.trim_start_matches('.').is_empty())
+                    .map(|ext| match ext.trim_start_matches('.') {
+                        _ if ext.eq_ignore_ascii_case(NO_EXTENSION) => String::new(),
+                        ext if case_sensitive => format!(".{}", ext),
+                        ext => format!(".{}", ext.to_lowercase()),
+                    })
+                    .collect();
+                if exts_vec.is_empty() {
+                    return Err(format!("Write some extensions, {} for the files without one, or use the {} mode", NO_EXTENSION, MatchMode::All.label()));
+                }
+                Ok(NameMatcher::Extensions(exts_vec, case_sensitive))
+            }
+            MatchMode::Glob => {
+                if pattern.is_empty() {
+                    return Err(String::from("Write a glob pattern"));
+                }
+                let whole_path = pattern.contains('/');
+                // In src/*.rs the * stays in src, ** goes into the subdirectories
+                let glob = GlobBuilder::new(pattern)
+                    .literal_separator(whole_path)
+                    .build()
+                    .map_err(|err| format!("Invalid glob: {}", err.kind()))?;
+                Ok(NameMatcher::Glob(glob.compile_matcher(), whole_path))
+            }
+            MatchMode::Regex => {
+                if pattern.is_empty() {
+                    return Err(String::from("Write a regular expression"));
+                }
+                let regex = Regex::new(pattern).map_err(regex_error)?;
+                Ok(NameMatcher::Regex(regex))
+            }
+            MatchMode::Fuzzy => {
+                if pattern.trim().is_empty() {
+                    return Err(String::from("Write something to search"));
+                }
+                Ok(NameMatcher::Fuzzy(FuzzyQuery::new(pattern.trim())))
+            }
+            MatchMode::All => Ok(NameMatcher::All),
+            MatchMode::Mime => Ok(NameMatcher::Mime(MimeQuery::new(pattern)?)),
+            MatchMode::Query => Ok(NameMatcher::Query(Box::new(Query::parse(pattern)?))),
+        }
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        match self {
+            NameMatcher::Extensions(exts, case_sensitive) => {
+                if let Some(name) = path.file_name() {
+                    let name = name.to_string_lossy();
+                    let name = if *case_sensitive { name } else { name.to_lowercase().into() };
+                    // Something must go before the extension, .gz alone is a hidden file without extension
+                    exts.iter().any(|ext| {
+                        // The directories have no extension either, but the token is for files like Makefile
+                        if ext.is_empty() {
+                            path.extension().is_none() && !path.is_dir()
+                        } else {
+                            name.len() > ext.len() && name.ends_with(ext.as_str())
+                        }
+                    })
+                } else {
+                    false
+                }
+            }
+            NameMatcher::Glob(glob, whole_path) => {
+                if *whole_path {
+                    glob.is_match(path)
+                } else {
+                    path.file_name().is_some_and(|name| glob.is_match(name))
+                }
+            }
+            NameMatcher::Regex(regex) => {
+                path.file_name().is_some_and(|name| regex.is_match(&name.to_string_lossy()))
+            }
+            NameMatcher::Fuzzy(query) => query.score(path).is_some(),
+            NameMatcher::All => true,
+            NameMatcher::Mime(query) => filetype::detect(path).is_some_and(|mime| query.matches(mime)),
+            NameMatcher::Query(query) => query.matches(path),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+// One line queries like `ext:rs,toml size>10k modified<30d -path:target name~"test"`
+use globset::GlobBuilder;
+use regex::RegexBuilder;
+use std::cell::OnceCell;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::filetype::{self, MimeQuery};
+use crate::filters::{parse_date, parse_group, parse_mode, parse_owner, parse_size, EntryKind, PermissionCheck, PermissionFilters, TypeFilter};
+use crate::matcher::{regex_error, MatchMode, NameMatcher};
+
+/// A filter expression, the terms written one after another must all match
+#[derive(Debug, Clone)]
+pub enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn holds<T: PartialOrd>(&self, value: T, limit: T) -> bool {
+        match self {
+            Comparison::Less => value < limit,
+            Comparison::LessOrEqual => value <= limit,
+            Comparison::Equal => value == limit,
+            Comparison::GreaterOrEqual => value >= limit,
+            Comparison::Greater => value > limit,
+        }
+    }
+
+    // The same comparison with the sides swapped, 30 days ago is an age but also a date
+    fn flip(&self) -> Comparison {
+        match self {
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+            Comparison::Equal => Comparison::Equal,
+            Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+            Comparison::Greater => Comparison::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DateKind {
+    Modified,
+    Created,
+    Accessed,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    // `ext:` and the names with wildcards
+    Name(Box<NameMatcher>),
+    // Lowercase, the bare words and `name:`
+    NameContains(String),
+    PathContains(String),
+    PathRegex(regex::Regex),
+    Size(Comparison, u64),
+    Date(DateKind, Comparison, SystemTime),
+    Mime(MimeQuery),
+    Type(TypeFilter),
+    Permissions(PermissionFilters),
+}
+
+// The metadata is only read when a term needs it, and only once
+struct Candidate<'a> {
+    path: &'a Path,
+    metadata: OnceCell<Option<Metadata>>,
+}
+
+impl Candidate<'_> {
+    fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.get_or_init(|| fs::metadata(self.path).ok()).as_ref()
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err(String::from("Write a query, like ext:rs size>10k"));
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some(Token::Close) => Err(String::from("There is a ) without its (")),
+            Some(_) => Err(String::from("Unexpected text in the query")),
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.evaluate(&Candidate { path, metadata: OnceCell::new() })
+    }
+
+    fn evaluate(&self, candidate: &Candidate) -> bool {
+        match self {
+            Query::And(queries) => queries.iter().all(|query| query.evaluate(candidate)),
+            Query::Or(queries) => queries.iter().any(|query| query.evaluate(candidate)),
+            Query::Not(query) => !query.evaluate(candidate),
+            Query::Term(term) => term.evaluate(candidate),
+        }
+    }
+}
+
+impl Term {
+    fn evaluate(&self, candidate: &Candidate) -> bool {
+        let path = candidate.path;
+        let name = || path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match self {
+            Term::Name(matcher) => matcher.is_match(path),
+            Term::NameContains(text) => name().contains(text.as_str()),
+            Term::PathContains(text) => path.to_string_lossy().to_lowercase().contains(text.as_str()),
+            Term::PathRegex(regex) => regex.is_match(&path.to_string_lossy()),
+            Term::Size(comparison, size) => {
+                candidate.metadata().is_some_and(|metadata| metadata.is_file() && comparison.holds(metadata.len(), *size))
+            }
+            Term::Date(kind, comparison, date) => {
+                let time = candidate.metadata().and_then(|metadata| match kind {
+                    DateKind::Modified => metadata.modified().ok(),
+                    DateKind::Created => metadata.created().ok(),
+                    DateKind::Accessed => metadata.accessed().ok(),
+                });
+                time.is_some_and(|time| comparison.holds(time, *date))
+            }
+            Term::Mime(query) => filetype::detect(path).is_some_and(|mime| query.matches(mime)),
+            Term::Type(filter) => EntryKind::of_path(path).is_some_and(|kind| filter.contains(kind)),
+            Term::Permissions(filters) => candidate.metadata().is_some_and(|metadata| filters.contains(metadata)),
+        }
+    }
+
+    // `key`, the comparison and the value of a term like size>10k
+    fn new(key: &str, operator: &str, value: &str) -> Result<Term, String> {
+        let comparison = match operator {
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            "=" => Comparison::Equal,
+            ">=" => Comparison::GreaterOrEqual,
+            ">" => Comparison::Greater,
+            _ => Comparison::Equal,
+        };
+        let wrong_operator = || format!("{}{} is not supported, see the query help in the README", key, operator);
+        if value.is_empty() {
+            return Err(format!("Write something after {}{}", key, operator));
+        }
+
+        match (key, operator) {
+            ("ext", ":") => {
+                let matcher = NameMatcher::new(MatchMode::Extensions, &value.replace(',', "|"), false)?;
+                Ok(Term::Name(Box::new(matcher)))
+            }
+            ("name", ":") if value.contains(['*', '?', '[']) => {
+                let glob = GlobBuilder::new(value)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| format!("Invalid glob: {}", err.kind()))?;
+                Ok(Term::Name(Box::new(NameMatcher::Glob(glob.compile_matcher(), false))))
+            }
+            ("name", ":") => Ok(Term::NameContains(value.to_lowercase())),
+            ("name", "~") => {
+                let regex = RegexBuilder::new(value).case_insensitive(true).build().map_err(regex_error)?;
+                Ok(Term::Name(Box::new(NameMatcher::Regex(regex))))
+            }
+            ("path", ":") => Ok(Term::PathContains(value.to_lowercase())),
+            ("path", "~") => {
+                let regex = RegexBuilder::new(value).case_insensitive(true).build().map_err(regex_error)?;
+                Ok(Term::PathRegex(regex))
+            }
+            ("size", ":" | "~") => Err(wrong_operator()),
+            ("size", _) => Ok(Term::Size(comparison, parse_size(value)?)),
+            ("modified" | "created" | "accessed", ":" | "~" | "=") => Err(wrong_operator()),
+            ("modified" | "created" | "accessed", _) => {
+                let kind = match key {
+                    "modified" => DateKind::Modified,
+                    "created" => DateKind::Created,
+                    _ => DateKind::Accessed,
+                };
+                let date = parse_date(value)?;
+                // Times ago like 30d compare the age, modified<30d is newer than 30 days
+                let is_age = value.starts_with(|c: char| c.is_ascii_digit()) && !value.contains(['-', ':']);
+                Ok(Term::Date(kind, if is_age { comparison.flip() } else { comparison }, date))
+            }
+            ("mime", ":") => Ok(Term::Mime(MimeQuery::new(&value.replace(',', "|"))?)),
+            ("type", ":") => Ok(Term::Type(TypeFilter::from_name(value)?)),
+            ("owner", ":") => Ok(Term::Permissions(PermissionFilters::new(Some(parse_owner(value)?), None, None, PermissionCheck::Any))),
+            ("group", ":") => Ok(Term::Permissions(PermissionFilters::new(None, Some(parse_group(value)?), None, PermissionCheck::Any))),
+            ("perm", ":") => {
+                let check = match value.to_lowercase().as_str() {
+                    "world-writable" => PermissionCheck::WorldWritable,
+                    "setuid" | "setgid" => PermissionCheck::SetId,
+                    "unreadable" => PermissionCheck::NotReadable,
+                    _ => return Ok(Term::Permissions(PermissionFilters::new(None, None, Some(parse_mode(value)?), PermissionCheck::Any))),
+                };
+                Ok(Term::Permissions(PermissionFilters::new(None, None, None, check)))
+            }
+            _ => Err(wrong_operator()),
+        }
+    }
+}
+
+const KEYS: [&str; 12] = ["ext", "name", "path", "size", "modified", "created", "accessed", "mime", "type", "owner", "group", "perm"];
+// The longer ones first, so <= isn't read as <
+const OPERATORS: [&str; 7] = ["<=", ">=", ":", "~", "<", ">", "="];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    // A term like size>10k, or a bare word searched in the names
+    Word(String),
+    // A bare word between quotes, it's never read as a term
+    Quoted(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '-' => {
+                chars.next();
+                match chars.peek() {
+                    Some(next) if !next.is_whitespace() => tokens.push(Token::Not),
+                    _ => return Err(String::from("Write a term right after the -, like -path:target")),
+                }
+            }
+            _ => {
+                let quoted = c == '"';
+                let mut word = String::new();
+                let mut in_quotes = false;
+                while let Some(&c) = chars.peek() {
+                    if !in_quotes && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    }
+                    chars.next();
+                    if c == '"' {
+                        in_quotes = !in_quotes;
+                    } else {
+                        word.push(c);
+                    }
+                }
+                if in_quotes {
+                    return Err(String::from("A quote is not closed"));
+                }
+                tokens.push(match word.as_str() {
+                    _ if quoted => Token::Quoted(word),
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent, OR binds looser than AND, which binds looser than NOT
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1).cloned()
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.and()?];
+        while self.tokens.get(self.pos) == Some(&Token::Or) {
+            self.pos += 1;
+            queries.push(self.and()?);
+        }
+        Ok(if queries.len() == 1 { queries.remove(0) } else { Query::Or(queries) })
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.unary()?];
+        loop {
+            match self.tokens.get(self.pos) {
+                None | Some(Token::Or) | Some(Token::Close) => break,
+                Some(Token::And) => self.pos += 1,
+                // Terms written one after another
+                Some(_) => {}
+            }
+            queries.push(self.unary()?);
+        }
+        Ok(if queries.len() == 1 { queries.remove(0) } else { Query::And(queries) })
+    }
+
+    fn unary(&mut self) -> Result<Query, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(String::from("There is a ( without its )")),
+                }
+            }
+            Some(Token::Quoted(word)) => Ok(Query::Term(Term::NameContains(word.to_lowercase()))),
+            Some(Token::Word(word)) => parse_term(&word).map(Query::Term),
+            Some(Token::And) | Some(Token::Or) => Err(String::from("AND and OR go between two terms")),
+            Some(Token::Close) => Err(String::from("There is an empty ( )")),
+            None => Err(String::from("The query ends too soon")),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, String> {
+    let key_end = word.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(word.len());
+    let (key, rest) = word.split_at(key_end);
+    let operator = OPERATORS.iter().find(|operator| rest.starts_with(*operator));
+    let key = key.to_lowercase();
+    match operator {
+        Some(operator) if KEYS.contains(&key.as_str()) => Term::new(&key, operator, &rest[operator.len()..]),
+        // Words like std::fs or a<b aren't filters, they are searched in the names
+        _ => Ok(Term::NameContains(word.to_lowercase())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn matches(query: &str, path: &str) -> bool {
+        Query::parse(query).unwrap().matches(Path::new(path))
+    }
+
+    // A temp dir of its own for every test, removed when the test ends, even when it fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("query-test-{}-{}", std::process::id(), test));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        // A file modified at the given time, with the path as the queries get it
+        fn modified_file(&self, name: &str, modified: SystemTime) -> String {
+            let path = self.0.join(name);
+            File::create(&path).unwrap().set_modified(modified).unwrap();
+            path.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn age_is_newer_than() {
+        let dir = TempDir::new("age");
+        let recent = &dir.modified_file("recent", SystemTime::now() - DAY);
+        let old = &dir.modified_file("old", SystemTime::now() - 60 * DAY);
+
+        assert!(matches("modified<30d", recent));
+        assert!(!matches("modified<30d", old));
+        assert!(matches("modified>30d", old));
+        assert!(!matches("modified>30d", recent));
+        assert!(matches("modified>=30d", old));
+    }
+
+    #[test]
+    fn date_is_before() {
+        let dir = TempDir::new("date");
+        let old = &dir.modified_file("2024", parse_date("2024-01-01").unwrap());
+        let recent = &dir.modified_file("now", SystemTime::now());
+
+        assert!(matches("modified<2024-01-31", old));
+        assert!(!matches("modified<2024-01-31", recent));
+        assert!(matches("modified>2024-01-31", recent));
+        assert!(matches("modified>\"last week\"", recent));
+    }
+
+    #[test]
+    fn negated_terms() {
+        assert!(!matches("-path:target", "/repo/target/debug/main.rs"));
+        assert!(matches("-path:target", "/repo/src/main.rs"));
+        assert!(matches("ext:rs -path:target", "/repo/src/main.rs"));
+        assert!(!matches("ext:rs NOT path:target", "/repo/target/main.rs"));
+        assert!(matches("-(ext:rs OR ext:toml)", "/repo/README.md"));
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        // (ext:rs name:main) OR ext:toml
+        let query = "ext:rs name:main OR ext:toml";
+        assert!(matches(query, "/repo/src/main.rs"));
+        assert!(matches(query, "/repo/Cargo.toml"));
+        assert!(!matches(query, "/repo/src/lib.rs"));
+        assert!(!matches(query, "/repo/main.md"));
+
+        let query = "ext:rs AND (name:main OR name:lib)";
+        assert!(matches(query, "/repo/src/lib.rs"));
+        assert!(!matches(query, "/repo/src/query.rs"));
+        assert!(!matches(query, "/repo/main.toml"));
+    }
+
+    #[test]
+    fn bare_words_search_the_names() {
+        assert!(matches("test", "/repo/tests/unit_test.rs"));
+        assert!(!matches("test", "/repo/tests/unit.rs"));
+        assert!(matches("std::fs", "/notes/std::fs.md"));
+        assert!(matches("\"ext:rs\"", "/notes/ext:rs"));
+        assert!(!matches("std::fs", "/notes/fs.md"));
+    }
+
+    #[test]
+    fn errors() {
+        for query in ["", "   ", "(ext:rs", "ext:rs)", "()", "ext:rs OR", "OR ext:rs", "- ext:rs", "\"open", "size:10k", "size>ten",
+                      "modified=30d", "modified>soon", "name~(", "ext:", "type:pipe", "perm:999"] {
+            assert!(Query::parse(query).is_err(), "{} should be an error", query);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use directories::ProjectDirs;
-use std::fs::{create_dir_all, File};
+use std::fs::{self, create_dir_all, File};
 use std::io::{Read,Write};
+use std::path::PathBuf;
 use chrono::Utc;
 use serde::{Serialize, Deserialize};
 use serde_yaml;
@@ -30,6 +31,41 @@ pub struct UIConfigurations {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GeneralConfigs {
     pub skip_metadata_errors: bool,
+    // 0 means one thread per core
+    #[serde(default)]
+    pub threads: usize,
+    // Bigger files are skipped when searching inside files
+    #[serde(default = "default_content_search_max_mb")]
+    pub content_search_max_mb: u64,
+    // Reads the start of the found files to show their type, it's slow on network drives
+    #[serde(default)]
+    pub detect_file_types: bool,
+    // Changed with the switches of the main page
+    #[serde(default)]
+    pub include_hidden: bool,
+    #[serde(default)]
+    pub respect_ignore_files: bool,
+    #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub same_file_system: bool,
+    // Skipped in every search, same syntax as the exclude field of the main page
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // Saved from the main page, shown after the built-in categories
+    #[serde(default)]
+    pub categories: Vec<ExtensionCategory>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExtensionCategory {
+    pub name: String,
+    // Separated by |, same as the extensions field of the main page
+    pub extensions: String,
+}
+
+fn default_content_search_max_mb() -> u64 {
+    32
 }
 
 pub fn setup() -> () {
@@ -69,7 +105,7 @@ pub fn setup() -> () {
 
                 let user_interface_base_config = UIConfigurations { enable_adw: true, color_scheme: String::from("dark") };
 
-                let gen_confs = GeneralConfigs { skip_metadata_errors: false };
+                let gen_confs = GeneralConfigs { skip_metadata_errors: false, threads: 0, content_search_max_mb: default_content_search_max_mb(), detect_file_types: false, include_hidden: false, respect_ignore_files: false, follow_links: false, same_file_system: false, exclude: vec![], categories: vec![] };
 
                 let general_conf = YamlConfiguration {
                     logs_configurations: logger_conf,
@@ -127,38 +163,75 @@ pub fn setup() -> () {
 }
 
 pub fn load_conf() -> YamlConfiguration {
-    let base_dir = ProjectDirs::from("", "", "FinderModernGUI");
-    let default_values = YamlConfiguration { logs_configurations: LogsConfigurations { write_to_stdout: false, write_to_file: true }, interface_configurations: UIConfigurations { enable_adw: true, color_scheme: String::from("dark") }, general: GeneralConfigs { skip_metadata_errors: false } };
+    let default_values = YamlConfiguration { logs_configurations: LogsConfigurations { write_to_stdout: false, write_to_file: true }, interface_configurations: UIConfigurations { enable_adw: true, color_scheme: String::from("dark") }, general: GeneralConfigs { skip_metadata_errors: false, threads: 0, content_search_max_mb: default_content_search_max_mb(), detect_file_types: false, include_hidden: false, respect_ignore_files: false, follow_links: false, same_file_system: false, exclude: vec![], categories: vec![] } };
+
+    match read_conf() {
+        Ok(confs) => confs,
+        Err(err) => {
+            eprintln!("Couldn't read config file. Defaulting config values...");
+            eprintln!("{}", err);
+            default_values
+        }
+    }
+}
 
+// Reads preferences.yml, the error tells why it couldn't be read
+fn read_conf() -> Result<YamlConfiguration, String> {
+    let base_dir = ProjectDirs::from("", "", "FinderModernGUI").ok_or("Base dir object is null")?;
+    let mut config_dir = base_dir.config_dir().to_path_buf();
+    config_dir.push("preferences.yml");
+
+    let mut buffer = String::new();
+    File::open(config_dir)
+        .and_then(|mut file| file.read_to_string(&mut buffer))
+        .map_err(|err| format!("Canno't read config file: {}", err))?;
+    serde_yaml::from_str(&buffer).map_err(|err| err.to_string())
+}
+
+/// Changes some settings and saves them. Nothing is written when preferences.yml
+/// can't be read, so a mistake in it doesn't turn the whole file into the defaults
+pub fn update_conf<F: FnOnce(&mut YamlConfiguration)>(change: F) {
+    match read_conf() {
+        Ok(mut configs) => {
+            change(&mut configs);
+            save_conf(&configs);
+        }
+        Err(err) => {
+            error!("Couldn't save config file, the saved one can't be read");
+            error!("{}", err);
+        }
+    }
+}
+
+// gitignore style file with patterns ignored in every search, next to preferences.yml
+pub fn ignore_file() -> Option<PathBuf> {
+    let base_dir = ProjectDirs::from("", "", "FinderModernGUI")?;
+    let mut ignore_file = base_dir.config_dir().to_path_buf();
+    ignore_file.push("ignore");
+    Some(ignore_file).filter(|file| file.exists())
+}
+
+pub fn save_conf(configs: &YamlConfiguration) {
+    let base_dir = ProjectDirs::from("", "", "FinderModernGUI");
     if base_dir.is_none() {
-        eprintln!("Couldn't read config file. Defaulting config values...");
-        eprintln!("Base dir object is null");
-        return default_values;
-    } else {
-        let mut config_dir = base_dir.clone().unwrap().config_dir().to_path_buf();
-        config_dir.push("preferences.yml");
-
-        match File::open(config_dir) {
-            Ok(mut file) => {
-                let mut buffer = String::new();
-                let content = file.read_to_string(&mut buffer);
-                if let Err(err) = content {
-                    eprintln!("Couldn't read config file. Defaulting config values...");
-                    eprintln!("Canno't read config file: {}", err);
-                    return default_values;
-                }
-                let confs: YamlConfiguration = serde_yaml::from_str(&buffer).unwrap_or_else(|err| {
-                    eprintln!("Couldn't read config file. Defaulting config values...");
-                    eprintln!("{}", err);
-                    return default_values;
-                });
-                return confs;
-            }
-            Err(err) => {
-                eprintln!("Couldn't read config file. Defaulting config values...");
-                eprintln!("Canno't open config file: {}", err);
-                return default_values;
+        error!("Couldn't save config file");
+        error!("Base dir object is null");
+        return;
+    }
+
+    let mut config_file = base_dir.unwrap().config_dir().to_path_buf();
+    config_file.push("preferences.yml");
+
+    match serde_yaml::to_string(configs) {
+        Ok(conf_yaml) => {
+            if let Err(err) = fs::write(config_file, conf_yaml) {
+                error!("Couldn't save config file");
+                error!("{}", err);
             }
         }
+        Err(err) => {
+            error!("Couldn't save config file");
+            error!("{}", err);
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,13 +1,19 @@
 use adw::prelude::*;
 use log::{debug, error};
-use std::{time::SystemTime, path::PathBuf};
+use std::time::SystemTime;
 use chrono::{DateTime, Local};
 use std::fs;
+use std::os::unix::fs::MetadataExt;
+use uzers::{Groups, Users, UsersCache};
 use gtk;
 
 use crate::setup::YamlConfiguration;
+use crate::duplicates::DuplicateGroup;
+use crate::empty::EmptyReport;
+use crate::du::DirUsage;
+use crate::finder::FoundEntry;
 
-fn convert_bytes_to_human_readable(bytes: u64) -> String {
+pub fn convert_bytes_to_human_readable(bytes: u64) -> String {
     if bytes < 1024 {
         return format!("{} B", bytes);
     }
@@ -18,9 +24,30 @@ fn convert_bytes_to_human_readable(bytes: u64) -> String {
     return format!("{:.1} {}B", bytes as f64 / 1024.0_f64.powi(exp * 10), unit);
 }
 
+// Like ls -l, rwsr-xr-x for a setuid executable
+fn format_mode(mode: u32) -> String {
+    let mut text = String::new();
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = mode >> shift;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
 pub struct Stated {
     liststore: gtk::ListStore,
+    // The reports made of groups of files, like the duplicates
+    treestore: gtk::TreeStore,
     configs: YamlConfiguration,
+    // The names of the owners, so they aren't looked up again for every file
+    users: UsersCache,
 }
 
 impl Stated {
@@ -31,13 +58,118 @@ impl Stated {
             String::static_type(),
             String::static_type(),
             String::static_type(),
-        ]), configs: configs }
+            // Fuzzy score, not shown
+            i64::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+        ]), treestore: gtk::TreeStore::new(&[
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+        ]), configs: configs, users: UsersCache::new() }
     }
 
     pub fn get_liststore(&self) -> gtk::ListStore{
         return self.liststore.clone();
     }
 
+    pub fn get_treestore(&self) -> gtk::TreeStore {
+        self.treestore.clone()
+    }
+
+    // One row per group, with the copies inside it
+    pub fn insert_duplicates(&self, groups: &[DuplicateGroup]) {
+        for group in groups {
+            let name = |path: &std::path::PathBuf| path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let size_f = convert_bytes_to_human_readable(group.size);
+            let group_f = format!("{} copies of {}", group.paths.len(), name(&group.paths[0]));
+            let wasted_f = convert_bytes_to_human_readable(group.wasted());
+            let parent = self.treestore.insert_with_values(None, None, &[
+                (0, &group_f.to_value()),
+                (2, &size_f.to_value()),
+                (3, &wasted_f.to_value()),
+            ]);
+            for path in &group.paths {
+                self.treestore.insert_with_values(Some(&parent), None, &[
+                    (0, &name(path).to_value()),
+                    (1, &path.to_string_lossy().to_string().to_value()),
+                    (2, &size_f.to_value()),
+                ]);
+            }
+        }
+    }
+
+    // One group per kind of entry, the groups without entries are left out
+    pub fn insert_empty_report(&self, report: &EmptyReport) {
+        let name = |path: &std::path::PathBuf| path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let groups = [
+            ("Empty files", report.files.iter().map(|path| (path, None)).collect::<Vec<_>>()),
+            ("Empty directories", report.dirs.iter().map(|path| (path, None)).collect()),
+            ("Broken symlinks", report.broken_links.iter().map(|(path, target)| (path, Some(target))).collect()),
+        ];
+        for (title, entries) in groups {
+            if entries.is_empty() {
+                continue;
+            }
+            let group_f = format!("{} ({})", title, entries.len());
+            let parent = self.treestore.insert_with_values(None, None, &[(0, &group_f.to_value())]);
+            for (path, target) in entries {
+                let target_f = target.map(|target| target.to_string_lossy().to_string()).unwrap_or_default();
+                self.treestore.insert_with_values(Some(&parent), None, &[
+                    (0, &name(path).to_value()),
+                    (1, &path.to_string_lossy().to_string().to_value()),
+                    (3, &target_f.to_value()),
+                ]);
+            }
+        }
+    }
+
+    // The directories inside each root, the percentages are of the parent directory
+    pub fn insert_disk_usage(&self, usage: &[DirUsage]) {
+        let total = usage.iter().map(|dir| dir.apparent).sum();
+        for dir in usage {
+            self.insert_dir_usage(None, dir, total);
+        }
+    }
+
+    fn insert_dir_usage(&self, parent: Option<&gtk::TreeIter>, dir: &DirUsage, parent_size: u64) {
+        // The roots are shown with their whole path
+        let name_f = match parent {
+            Some(_) => dir.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+            None => dir.path.to_string_lossy().to_string(),
+        };
+        let percentage = if parent_size > 0 { dir.apparent as f64 * 100.0 / parent_size as f64 } else { 0.0 };
+        let percentage_f = format!("{:.1} %", percentage);
+        let iter = self.treestore.insert_with_values(parent, None, &[
+            (0, &name_f.to_value()),
+            (1, &dir.path.to_string_lossy().to_string().to_value()),
+            (2, &convert_bytes_to_human_readable(dir.apparent).to_value()),
+            (3, &convert_bytes_to_human_readable(dir.on_disk).to_value()),
+            (4, &percentage_f.to_value()),
+        ]);
+        for child in &dir.children {
+            self.insert_dir_usage(Some(&iter), child, dir.apparent);
+        }
+    }
+
+    // Keeps the best fuzzy matches on top, even the ones inserted later
+    pub fn sort_by_score(&self) {
+        self.liststore.set_sort_column_id(gtk::SortColumn::Index(5), gtk::SortType::Descending);
+    }
+
+    // Keeps the same order between runs, whatever thread found the files first
+    pub fn sort_by_path(&self) {
+        self.liststore.set_sort_column_id(gtk::SortColumn::Index(2), gtk::SortType::Ascending);
+    }
+
     pub fn insert_into(&self, index: u32, data: String) -> gtk::TreeIter {
         self.liststore.insert_with_values(
             None,
@@ -47,17 +179,21 @@ impl Stated {
         )
     }
 
-    pub fn stat_and_insert(&self, files: Vec<PathBuf>) -> Result<(), String> {
+    pub fn stat_and_insert(&self, files: Vec<FoundEntry>) -> Result<(), String> {
         debug!("Getting information of {} files", files.len());
-        for (_, file) in files.iter().enumerate() {
-            let metadata = fs::metadata(file.as_path().to_string_lossy().to_string());
+        for (_, entry) in files.iter().enumerate() {
+            let file = &entry.path;
+            // The broken symlinks have no target, the symlink itself is shown
+            let target_metadata = fs::metadata(file);
+            let is_broken = target_metadata.is_err();
+            let metadata = target_metadata.or_else(|_| fs::symlink_metadata(file));
 
             if let Err(err) = metadata {
                 if !self.configs.general.skip_metadata_errors {
                     error!("Error while trying to get metadata of the file {}. Error message: {}", file.as_path().to_string_lossy(), err);
                     return Err(format!("Cannot get information of one file {}", file.as_path().to_string_lossy()));
                 } else {
-                    return Ok(());
+                    continue;
                 }
             }
 
@@ -69,9 +205,26 @@ impl Stated {
             
             let date_f = datetime.to_string();
             let size_f = convert_bytes_to_human_readable(metadata.len());
-            let ext_f = file.extension().unwrap().to_string_lossy().to_string();
+            let ext_f = file.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default();
             let name_f = file.file_name().unwrap().to_string_lossy().to_string();
             let path_f = file.as_path().to_string_lossy().to_string();
+            let line_f = entry.content.as_ref().map(|content| content.line.to_string()).unwrap_or_default();
+            let snippet_f = entry.content.as_ref().map(|content| content.snippet.clone()).unwrap_or_default();
+            let root_f = entry.root.to_string_lossy().to_string();
+            let mime_f = entry.mime.unwrap_or_default();
+            let kind_f = entry.kind.label();
+            // Same as the permission filters, which check the target of the symlinks
+            let (owner_f, group_f, permissions_f) = if is_broken {
+                Default::default()
+            } else {
+                let owner_f = self.users.get_user_by_uid(metadata.uid())
+                    .map(|user| user.name().to_string_lossy().to_string())
+                    .unwrap_or_else(|| metadata.uid().to_string());
+                let group_f = self.users.get_group_by_gid(metadata.gid())
+                    .map(|group| group.name().to_string_lossy().to_string())
+                    .unwrap_or_else(|| metadata.gid().to_string());
+                (owner_f, group_f, format_mode(metadata.mode()))
+            };
             
             self.liststore.insert_with_values(None, &[
                 (0, &name_f.to_value()),
@@ -79,6 +232,15 @@ impl Stated {
                 (2, &path_f.to_value()),
                 (3, &size_f.to_value()),
                 (4, &date_f.to_value()),
+                (5, &entry.score.unwrap_or(0).to_value()),
+                (6, &line_f.to_value()),
+                (7, &snippet_f.to_value()),
+                (8, &root_f.to_value()),
+                (9, &mime_f.to_value()),
+                (10, &kind_f.to_value()),
+                (11, &owner_f.to_value()),
+                (12, &group_f.to_value()),
+                (13, &permissions_f.to_value()),
             ]);
             
         }